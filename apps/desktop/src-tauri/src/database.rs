@@ -1,13 +1,165 @@
-use rusqlite::{Connection, Result, OptionalExtension};
+use rusqlite::{Connection, DatabaseName, OpenFlags, Result, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 use tauri::State;
 use chrono;
+use sha2::{Digest, Sha256};
+use unicode_segmentation::UnicodeSegmentation;
+use aes_gcm::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
 
 // Thread-safe database connection wrapper
+// Every command is a sync #[tauri::command], which Tauri runs on the main thread, so commands
+// never contend with each other. The vault watcher thread still takes the lock to poll
+// data_version, and the metrics wrapper takes it after settings writes, so keep each lock
+// short (no I/O beyond SQLite while holding it). A dedicated DB thread fed over a channel
+// was considered and not adopted: it would only pay off once commands become async
 pub struct DbConnection(pub Mutex<Option<Connection>>);
 
+/// Soft-deletes made this session, newest last, for undo_last_delete
+/// Kept in memory beside the connection: it's per session and never needs to survive a restart
+#[derive(Default)]
+pub struct DeleteHistory(pub Mutex<VecDeque<DeletedEntity>>);
+
+/// How many soft-deletes undo_last_delete can walk back
+const DELETE_HISTORY_DEPTH: usize = 20;
+
+impl DeleteHistory {
+    fn push(&self, entity_type: &str, id: &str, deleted_at: String) {
+        let mut entries = self.0.lock().unwrap();
+        if entries.len() == DELETE_HISTORY_DEPTH {
+            entries.pop_front();
+        }
+        entries.push_back(DeletedEntity {
+            entity_type: entity_type.to_string(),
+            id: id.to_string(),
+            deleted_at,
+        });
+    }
+}
+
+/// Database errors that deserve a clearer message than the raw SQLite error
+/// Commands still return `Result<_, String>`; `?` converts via `From<DbError> for String`
+#[derive(Debug)]
+pub enum DbError {
+    /// No connection is open (init_database not called yet, or close_database was)
+    NotInitialized,
+    /// The passphrase didn't decrypt the database (or the file isn't a database)
+    WrongPassphrase,
+    /// A passphrase was supplied but this build wasn't compiled with SQLCipher
+    EncryptionUnsupported,
+    /// The database path is empty or points at a directory
+    InvalidPath(String),
+    /// Filesystem error while preparing the database location
+    Io { path: String, source: std::io::Error },
+    /// The database was opened in read-only safe mode and a write was attempted
+    ReadOnly,
+    /// A field failed validation before reaching SQLite
+    Validation(String),
+    /// Note content exceeds the `limits.max_note_bytes` setting
+    TooLarge { actual: usize, allowed: usize },
+    Sql(rusqlite::Error),
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::NotInitialized => write!(f, "Database not initialized"),
+            DbError::WrongPassphrase => write!(f, "Wrong passphrase or not an encrypted Clutter database"),
+            DbError::EncryptionUnsupported => {
+                write!(f, "Encryption is not supported by this build (requires the `sqlcipher` feature)")
+            }
+            DbError::InvalidPath(path) => write!(f, "Invalid database path: '{}'", path),
+            DbError::Io { path, source } => write!(f, "I/O error at '{}': {}", path, source),
+            DbError::ReadOnly => write!(f, "Database is open read-only (safe mode); changes are disabled"),
+            DbError::Validation(message) => write!(f, "{}", message),
+            DbError::TooLarge { actual, allowed } => write!(
+                f,
+                "Note content is too large: {} bytes (limit is {} bytes)",
+                actual, allowed
+            ),
+            DbError::Sql(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Sql(e)
+    }
+}
+
+impl From<DbError> for String {
+    fn from(e: DbError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Validate the database path and create its parent directory on first run
+/// `:memory:` is passed through untouched
+fn prepare_db_path(db_path: &str) -> Result<(), DbError> {
+    let trimmed = db_path.trim();
+    if trimmed.is_empty() || trimmed == "." || trimmed == "./" {
+        return Err(DbError::InvalidPath(db_path.to_string()));
+    }
+    if trimmed == ":memory:" {
+        return Ok(());
+    }
+    
+    let path = Path::new(db_path);
+    if path.is_dir() {
+        return Err(DbError::InvalidPath(db_path.to_string()));
+    }
+    
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if !parent.exists() {
+            std::fs::create_dir_all(parent).map_err(|source| DbError::Io {
+                path: parent.display().to_string(),
+                source,
+            })?;
+            println!("📁 Created database directory: {}", parent.display());
+        }
+    }
+    
+    Ok(())
+}
+
+/// Fail fast before a write when the connection was opened in read-only safe mode
+fn ensure_writable(conn: &Connection) -> Result<(), DbError> {
+    if conn.is_readonly(DatabaseName::Main)? {
+        return Err(DbError::ReadOnly);
+    }
+    Ok(())
+}
+
+/// Unlock an SQLCipher database; must run before any other statement on the connection
+/// A wrong key only surfaces on the first real read, so probe sqlite_master right away
+fn apply_passphrase(conn: &Connection, passphrase: &str) -> Result<(), DbError> {
+    // Plain SQLite silently ignores PRAGMA key - refuse rather than pretend to encrypt
+    let cipher_version: Option<String> = conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .optional()?;
+    if cipher_version.is_none() {
+        return Err(DbError::EncryptionUnsupported);
+    }
+    
+    conn.pragma_update(None, "key", passphrase)?;
+    
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(err, _)) if err.code == rusqlite::ErrorCode::NotADatabase => {
+            Err(DbError::WrongPassphrase)
+        }
+        Err(e) => Err(DbError::Sql(e)),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Note {
@@ -25,6 +177,51 @@ pub struct Note {
     pub created_at: String,
     pub updated_at: String,
     pub deleted_at: Option<String>,
+    /// Manual position within a folder (None until the user reorders)
+    /// Owned by reorder_note - save_note never overwrites it
+    #[serde(default)]
+    pub sort_order: Option<f64>,
+    /// Pinned notes float to the top of their folder (owned by set_note_pinned)
+    #[serde(default)]
+    pub is_pinned: bool,
+    /// Locked notes carry LOCKED_NOTE_PLACEHOLDER as content (owned by lock_note/unlock_note)
+    #[serde(default)]
+    pub is_locked: bool,
+    /// Source note id when this note was made by copy_note_to_folder (owned by that command)
+    #[serde(default)]
+    pub copied_from: Option<String>,
+}
+
+/// Lightweight note listing for the sidebar (everything except `content`)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteMeta {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub description_visible: bool,
+    pub emoji: Option<String>,
+    pub tags: Vec<String>,
+    pub tags_visible: bool,
+    pub is_favorite: bool,
+    pub folder_id: Option<String>,
+    pub daily_note_date: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub deleted_at: Option<String>,
+    pub is_locked: bool,
+    pub is_pinned: bool,
+    pub sort_order: Option<f64>,
+}
+
+/// Short plain-text snippet of a note for list rows (see load_note_previews)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePreview {
+    pub id: String,
+    pub title: String,
+    pub emoji: Option<String>,
+    pub preview: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,842 +256,7035 @@ pub struct Tag {
     pub deleted_at: Option<String>,
 }
 
-/// Initialize database at the specified path
-#[tauri::command]
-pub fn init_database(db_path: String, state: State<DbConnection>) -> Result<String, String> {
-    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbStats {
+    pub total_notes: i64,
+    pub deleted_notes: i64,
+    pub total_folders: i64,
+    pub total_tags: i64,
+    pub favorites: i64,
+    pub notes_per_tag: HashMap<String, i64>,
+}
+
+/// What's sitting in the trash (soft-deleted notes and folders)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashSummary {
+    pub note_count: i64,
+    pub folder_count: i64,
+    pub oldest_deleted_at: Option<String>,
+    /// UTF-8 bytes of trashed note content ("reclaim 14MB")
+    pub total_content_bytes: i64,
+}
+
+/// Storage used by a folder subtree (see get_folder_size)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderSize {
+    pub note_count: i64,
+    /// UTF-8 bytes of note content, plus the ciphertext of locked notes
+    pub total_content_bytes: i64,
+    /// Always 0: this schema has no attachments table yet
+    pub attachment_bytes: i64,
+}
+
+/// Counts from clear_trash
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashCleared {
+    pub notes_deleted: usize,
+    pub folders_deleted: usize,
+}
+
+/// Items touched by a cascading folder delete/restore
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CascadeSummary {
+    pub folders: usize,
+    pub notes: usize,
+}
+
+/// How delete_folder_permanently's orphan policy handled the folder's notes
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanedNotes {
+    pub reparented: usize,
+    pub trashed: usize,
+}
+
+/// Notes and folders whose tags changed as a side effect (frontend should refresh them)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AffectedIds {
+    pub note_ids: Vec<String>,
+    pub folder_ids: Vec<String>,
+}
+
+/// Favorited notes and folders for the sidebar's favorites section
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Favorites {
+    pub notes: Vec<Note>,
+    pub folders: Vec<Folder>,
+}
+
+/// Adjacent note ids for previous/next navigation
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Neighbors {
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+/// An editor noteLink whose target is gone (see get_broken_links)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenLink {
+    pub source_note_id: String,
+    /// "note" or "folder", from the link's linkType attribute
+    pub link_type: String,
+    pub target_id: String,
+    /// Text the link displays (the target's title when it was inserted)
+    pub label: String,
+    /// "missing" (deleted permanently or never existed) or "trashed"
+    pub reason: String,
+}
+
+/// Junction rows removed by cleanup_orphaned_relationships, per category
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationshipCleanup {
+    pub note_tags_without_note: usize,
+    pub folder_tags_without_folder: usize,
+    pub note_tags_without_tag: usize,
+    pub folder_tags_without_tag: usize,
+}
+
+/// Where backup_database wrote the copy and how big it is
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub path: String,
+    pub size_bytes: u64,
+}
+
+/// A permanent delete recorded for sync ("note", "folder" or "tag")
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub entity_type: String,
+    pub entity_id: String,
+    pub deleted_at: String,
+}
+
+/// One page of search_notes results plus the total match count ("1–50 of 312")
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    pub notes: Vec<Note>,
+    pub total: i64,
+}
+
+/// Result of ensure_daily_note / open_daily_note; `created` tells the UI to focus the editor
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyNoteResult {
+    pub note: Note,
+    pub created: bool,
+}
+
+/// One save_audit row: content byte lengths before and after a save (see get_save_audit)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveAuditEntry {
+    /// None when the save created the note
+    pub old_length: Option<i64>,
+    pub new_length: i64,
+    /// Command that wrote the content (save_note, update_note_content, replace_in_note, ...)
+    pub source: String,
+    pub saved_at: String,
+}
+
+/// Reusable note skeleton (meeting notes, book notes); applied by create_note_from_template
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    pub content: String,
+    pub emoji: Option<String>,
+    #[serde(default)]
+    pub default_tags: Vec<String>,
+    pub created_at: String,
+}
+
+/// A folder plus whether it has (non-deleted) subfolders, for lazy tree expansion
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderNode {
+    #[serde(flatten)]
+    pub folder: Folder,
+    pub has_children: bool,
+}
+
+/// What import_markdown_directory created
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub notes_created: usize,
+    pub folders_created: usize,
+    pub files_skipped: usize,
+}
+
+/// What export_folder_to_zip wrote
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZipExport {
+    pub path: String,
+    /// Markdown note files in the archive (manifest.json not counted)
+    pub file_count: usize,
+}
+
+/// Optional SQLite tuning passed to init_database
+/// Every field falls back to the defaults below when omitted
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitOptions {
+    pub journal_mode: Option<String>,
+    pub synchronous: Option<String>,
+    pub cache_size_kb: Option<i64>,
+    pub foreign_keys: Option<bool>,
+    /// Safe mode for recovery: open read-only and skip all schema setup
+    pub read_only: Option<bool>,
+    /// FTS tokenizer: "unicode61", "porter" (English stemming) or "trigram" (substring / CJK)
+    /// Changing it rebuilds the search index; omitted keeps whatever the database already uses
+    pub fts_tokenizer: Option<String>,
+    /// WAL pages between automatic checkpoints (0 disables them); persisted as `wal.autocheckpoint_pages`
+    /// Omitted keeps the stored setting, or SQLite's default of 1000
+    pub wal_autocheckpoint: Option<i64>,
+    /// Keep a second, trigram-tokenized index so `search_notes(substring)` can match inside words
+    /// Trigram indexes are several times larger than the token index; persisted as `fts.substring_index`
+    pub substring_index: Option<bool>,
+}
+
+impl InitOptions {
+    /// Check every option without applying any
+    fn validate(&self) -> Result<(), String> {
+        self.journal_mode()?;
+        self.synchronous()?;
+        self.cache_size_kb()?;
+        self.fts_tokenizer()?;
+        self.wal_autocheckpoint()?;
+        Ok(())
+    }
     
-    // Enable foreign key constraints (critical for referential integrity)
-    conn.execute("PRAGMA foreign_keys = ON", [])
-        .map_err(|e| e.to_string())?;
+    /// Validated journal mode (default: WAL)
+    fn journal_mode(&self) -> Result<&'static str, String> {
+        match self.journal_mode.as_deref().map(|m| m.to_uppercase()).as_deref() {
+            None | Some("WAL") => Ok("WAL"),
+            Some("DELETE") => Ok("DELETE"),
+            Some("MEMORY") => Ok("MEMORY"),
+            Some(other) => Err(format!(
+                "Invalid journal_mode '{}': expected WAL, DELETE or MEMORY",
+                other
+            )),
+        }
+    }
     
-    // Configure SQLite for optimal local-only performance (Apple Notes approach)
-    // WAL mode: Fast writes, concurrent reads
-    conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(())).ok();
-    // NORMAL synchronous: Balance between safety and speed (safe for local apps)
-    conn.query_row("PRAGMA synchronous = NORMAL", [], |_| Ok(())).ok();
-    // Suggested page cache size: ~8MB (2000 pages * 4KB)
-    conn.query_row("PRAGMA cache_size = -8000", [], |_| Ok(())).ok();
+    /// Validated synchronous level (default: NORMAL)
+    fn synchronous(&self) -> Result<&'static str, String> {
+        match self.synchronous.as_deref().map(|m| m.to_uppercase()).as_deref() {
+            None | Some("NORMAL") => Ok("NORMAL"),
+            Some("OFF") => Ok("OFF"),
+            Some("FULL") => Ok("FULL"),
+            Some("EXTRA") => Ok("EXTRA"),
+            Some(other) => Err(format!(
+                "Invalid synchronous '{}': expected OFF, NORMAL, FULL or EXTRA",
+                other
+            )),
+        }
+    }
     
-    // Create notes table (IF NOT EXISTS - preserves data on restart)
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS notes (
-            id TEXT PRIMARY KEY,
-            title TEXT NOT NULL,
-            description TEXT NOT NULL,
-            description_visible INTEGER NOT NULL,
-            emoji TEXT,
-            content TEXT NOT NULL,
-            tags_visible INTEGER NOT NULL,
-            is_favorite INTEGER NOT NULL,
-            folder_id TEXT,
-            daily_note_date TEXT,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            deleted_at TEXT
-        )",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+    /// Validated FTS5 `tokenize` spec, if one was requested
+    fn fts_tokenizer(&self) -> Result<Option<&'static str>, String> {
+        match self.fts_tokenizer.as_deref().map(|t| t.trim().to_lowercase()).as_deref() {
+            None => Ok(None),
+            Some("unicode61") => Ok(Some("unicode61")),
+            Some("porter") | Some("porter unicode61") => Ok(Some("porter unicode61")),
+            Some("trigram") => Ok(Some("trigram")),
+            Some(other) => Err(format!(
+                "Invalid fts_tokenizer '{}': expected unicode61, porter or trigram",
+                other
+            )),
+        }
+    }
     
-    // Create folders table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS folders (
-            id TEXT PRIMARY KEY,
-            name TEXT NOT NULL,
-            parent_id TEXT,
-            description TEXT NOT NULL,
-            description_visible INTEGER NOT NULL,
-            color TEXT,
-            emoji TEXT,
-            tags_visible INTEGER NOT NULL,
-            is_favorite INTEGER NOT NULL,
-            is_expanded INTEGER NOT NULL,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            deleted_at TEXT,
-            FOREIGN KEY (parent_id) REFERENCES folders(id)
-        )",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+    /// Validated WAL autocheckpoint threshold in pages, if one was requested
+    fn wal_autocheckpoint(&self) -> Result<Option<i64>, String> {
+        match self.wal_autocheckpoint {
+            Some(pages) if pages < 0 => Err(format!("Invalid wal_autocheckpoint {}: must not be negative", pages)),
+            pages => Ok(pages),
+        }
+    }
     
-    // Create tags table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS tags (
-            name TEXT PRIMARY KEY,
-            description TEXT NOT NULL,
-            description_visible INTEGER NOT NULL,
-            is_favorite INTEGER NOT NULL,
-            color TEXT,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL,
-            deleted_at TEXT
-        )",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+    /// Validated page cache size in KB (default: ~8MB)
+    fn cache_size_kb(&self) -> Result<i64, String> {
+        match self.cache_size_kb {
+            None => Ok(8000),
+            Some(kb) if kb > 0 => Ok(kb),
+            Some(kb) => Err(format!("Invalid cache_size_kb {}: must be positive", kb)),
+        }
+    }
+}
+
+// Column list shared by every note SELECT (order matches note_from_row)
+// Qualified with the table name so it also works in FTS joins
+const NOTE_COLUMNS: &str = "notes.id, notes.title, notes.description, notes.description_visible, 
+             notes.emoji, notes.content, notes.tags_visible, notes.is_favorite, 
+             notes.folder_id, notes.daily_note_date, notes.created_at, notes.updated_at, 
+             notes.deleted_at, notes.sort_order, notes.is_pinned, notes.is_locked, 
+             notes.copied_from";
+
+/// Map a row selected with NOTE_COLUMNS into a Note (tags left empty)
+fn note_from_row(row: &rusqlite::Row) -> Result<Note> {
+    Ok(Note {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        description_visible: row.get::<_, i32>(3)? != 0,
+        emoji: row.get(4)?,
+        content: row.get(5)?,
+        tags: Vec::new(), // Populated by caller
+        tags_visible: row.get::<_, i32>(6)? != 0,
+        is_favorite: row.get::<_, i32>(7)? != 0,
+        folder_id: row.get(8)?,
+        daily_note_date: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        deleted_at: row.get(12)?,
+        sort_order: row.get(13)?,
+        is_pinned: row.get::<_, i32>(14)? != 0,
+        is_locked: row.get::<_, i32>(15)? != 0,
+        copied_from: row.get(16)?,
+    })
+}
+
+/// Group note_tags rows by note id
+/// `note_ids = None` loads every relationship in ONE query (Apple Notes approach - no N+1!)
+fn load_tags_by_note(
+    conn: &Connection,
+    note_ids: Option<&[String]>,
+) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut tags_by_note: HashMap<String, Vec<String>> = HashMap::new();
     
-    // Add deleted_at column to existing tags table (migration)
-    // This will fail silently if the column already exists
-    let _ = conn.execute(
-        "ALTER TABLE tags ADD COLUMN deleted_at TEXT",
-        [],
-    );
+    let query_str = match note_ids {
+        None => "SELECT note_id, tag_name FROM note_tags".to_string(),
+        Some([]) => return Ok(tags_by_note),
+        Some(ids) => {
+            let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            format!("SELECT note_id, tag_name FROM note_tags WHERE note_id IN ({})", placeholders)
+        }
+    };
     
-    // Create note_tags junction table (many-to-many)
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS note_tags (
-            note_id TEXT NOT NULL,
-            tag_name TEXT NOT NULL,
-            PRIMARY KEY (note_id, tag_name),
-            FOREIGN KEY (note_id) REFERENCES notes(id) ON DELETE CASCADE,
-            FOREIGN KEY (tag_name) REFERENCES tags(name) ON DELETE CASCADE
-        )",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+    let mut tag_stmt = conn.prepare(&query_str).map_err(|e| e.to_string())?;
+    let tag_rows = tag_stmt
+        .query_map(rusqlite::params_from_iter(note_ids.unwrap_or_default()), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
     
-    // Create folder_tags junction table (many-to-many)
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS folder_tags (
-            folder_id TEXT NOT NULL,
-            tag_name TEXT NOT NULL,
-            PRIMARY KEY (folder_id, tag_name),
-            FOREIGN KEY (folder_id) REFERENCES folders(id) ON DELETE CASCADE,
-            FOREIGN KEY (tag_name) REFERENCES tags(name) ON DELETE CASCADE
+    // Group tags by note_id in memory (fast HashMap lookup)
+    for result in tag_rows {
+        let (note_id, tag) = result.map_err(|e| e.to_string())?;
+        tags_by_note.entry(note_id).or_default().push(tag);
+    }
+    
+    Ok(tags_by_note)
+}
+
+/// Batch-load tags for a set of notes
+fn populate_note_tags(conn: &Connection, notes: &mut [Note]) -> Result<(), String> {
+    let note_ids: Vec<String> = notes.iter().map(|n| n.id.clone()).collect();
+    let mut tags_by_note = load_tags_by_note(conn, Some(&note_ids))?;
+    
+    // Assign tags to notes (O(1) lookup per note)
+    for note in notes.iter_mut() {
+        note.tags = tags_by_note.remove(&note.id).unwrap_or_default();
+    }
+    
+    Ok(())
+}
+
+// Column list shared by every folder SELECT (order matches folder_from_row)
+const FOLDER_COLUMNS: &str = "id, name, parent_id, description, description_visible, color, emoji, 
+             tags_visible, is_favorite, is_expanded, created_at, updated_at, deleted_at";
+
+// Column list shared by every tag SELECT (order matches tag_from_row)
+const TAG_COLUMNS: &str = "name, description, description_visible, is_favorite, color, created_at, updated_at, deleted_at";
+
+// Upper bound on hierarchy walks so a corrupted parent chain can't hang a command
+const MAX_FOLDER_DEPTH: usize = 256;
+
+/// Escape `%`, `_` and `\` so user text can be embedded in a LIKE pattern (use with ESCAPE '\')
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Plain search words from an FTS query: drops operators and FTS punctuation
+fn search_terms(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "AND" | "OR" | "NOT" | "NEAR"))
+        .map(|word| {
+            word.chars()
+                .filter(|c| !matches!(c, '"' | '*' | '(' | ')' | '^' | ':' | '+' | '-'))
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// UNION branch for search_notes: notes carrying an active tag whose name or description
+/// contains every search term (params ?2.. are the LIKE patterns), excluding hits in `fts_table`
+fn tag_search_branch(term_count: usize, fts_table: &str) -> String {
+    if term_count == 0 {
+        return String::new();
+    }
+    
+    let conditions = (0..term_count)
+        .map(|i| {
+            format!(
+                "(tags.name LIKE ?{0} ESCAPE '\\' OR tags.description LIKE ?{0} ESCAPE '\\')",
+                i + 2
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    
+    format!(
+        "UNION ALL
+         SELECT notes.*, 1 AS match_group, 0 AS match_rank
+         FROM notes
+         WHERE notes.deleted_at IS NULL
+           AND notes.id IN (
+               SELECT note_tags.note_id FROM note_tags
+               JOIN tags ON tags.name = note_tags.tag_name
+               WHERE tags.deleted_at IS NULL AND {0}
+           )
+           AND notes.id NOT IN (SELECT note_id FROM {1} WHERE {1} MATCH ?1)",
+        conditions, fts_table
+    )
+}
+
+/// Recursive CTE selecting `subtree(id, depth)` for the folder bound to ?1 and all descendants
+/// Depth-capped so a parent_id cycle terminates
+fn folder_subtree_cte() -> String {
+    format!(
+        "WITH RECURSIVE subtree(id, depth) AS (
+            SELECT id, 0 FROM folders WHERE id = ?1
+            UNION
+            SELECT folders.id, subtree.depth + 1
+            FROM folders JOIN subtree ON folders.parent_id = subtree.id
+            WHERE subtree.depth < {}
+        )",
+        MAX_FOLDER_DEPTH
+    )
+}
+
+/// Reject a parent folder that's in the trash (a child under it would vanish from the tree)
+/// Returns false when the parent doesn't exist, so each caller decides how to report that
+fn ensure_parent_not_trashed(conn: &Connection, parent_id: &str) -> Result<bool, String> {
+    let parent_deleted_at: Option<Option<String>> = conn
+        .prepare_cached("SELECT deleted_at FROM folders WHERE id = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row([parent_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match parent_deleted_at {
+        None => Ok(false),
+        Some(Some(_)) => Err(DbError::Validation(format!("Parent folder '{}' is in the trash", parent_id)).into()),
+        Some(None) => Ok(true),
+    }
+}
+
+/// Reject reparenting `folder_id` under `new_parent_id` when that would create a cycle,
+/// i.e. when the folder appears in the new parent's ancestry (or is the parent itself)
+/// Same visited-set + depth-cap guard as get_folder_path
+fn ensure_no_folder_cycle(conn: &Connection, folder_id: &str, new_parent_id: &str) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare_cached("SELECT parent_id FROM folders WHERE id = ?1")
+        .map_err(|e| e.to_string())?;
+    
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut current_id = Some(new_parent_id.to_string());
+    
+    while let Some(id) = current_id {
+        if id == folder_id {
+            return Err(format!(
+                "Cannot move folder '{}' into '{}': it would become its own ancestor",
+                folder_id, new_parent_id
+            ));
+        }
+        if !visited.insert(id.clone()) || visited.len() > MAX_FOLDER_DEPTH {
+            return Err(format!("Folder hierarchy above '{}' is corrupted (cycle or too deep)", new_parent_id));
+        }
+        
+        current_id = stmt
+            .query_row([&id], |row| row.get::<_, Option<String>>(0))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .flatten();
+    }
+    
+    Ok(())
+}
+
+/// Map a row selected with FOLDER_COLUMNS into a Folder (tags left empty)
+fn folder_from_row(row: &rusqlite::Row) -> Result<Folder> {
+    Ok(Folder {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        parent_id: row.get(2)?,
+        description: row.get(3)?,
+        description_visible: row.get::<_, i32>(4)? != 0,
+        color: row.get(5)?,
+        emoji: row.get(6)?,
+        tags: Vec::new(), // Populated by caller
+        tags_visible: row.get::<_, i32>(7)? != 0,
+        is_favorite: row.get::<_, i32>(8)? != 0,
+        is_expanded: row.get::<_, i32>(9)? != 0,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        deleted_at: row.get(12)?,
+    })
+}
+
+/// Map a row selected with TAG_COLUMNS into a Tag
+fn tag_from_row(row: &rusqlite::Row) -> Result<Tag> {
+    Ok(Tag {
+        name: row.get(0)?,
+        description: row.get(1)?,
+        description_visible: row.get::<_, i32>(2)? != 0,
+        is_favorite: row.get::<_, i32>(3)? != 0,
+        color: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        deleted_at: row.get(7)?,
+    })
+}
+
+/// Batch-load tags for a set of folders in ONE query (no N+1)
+fn populate_folder_tags(conn: &Connection, folders: &mut [Folder]) -> Result<(), String> {
+    if folders.is_empty() {
+        return Ok(());
+    }
+    
+    let folder_ids: Vec<String> = folders.iter().map(|f| f.id.clone()).collect();
+    let placeholders = folder_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let query_str = format!("SELECT folder_id, tag_name FROM folder_tags WHERE folder_id IN ({})", placeholders);
+    
+    let mut tag_stmt = conn.prepare(&query_str).map_err(|e| e.to_string())?;
+    let tag_rows = tag_stmt
+        .query_map(rusqlite::params_from_iter(folder_ids.iter()), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    
+    let mut tags_by_folder: HashMap<String, Vec<String>> = HashMap::new();
+    for result in tag_rows {
+        let (folder_id, tag) = result.map_err(|e| e.to_string())?;
+        tags_by_folder.entry(folder_id).or_default().push(tag);
+    }
+    
+    for folder in folders.iter_mut() {
+        folder.tags = tags_by_folder.remove(&folder.id).unwrap_or_default();
+    }
+    
+    Ok(())
+}
+
+/// Read a raw value from the settings table
+fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.prepare_cached("SELECT value FROM settings WHERE key = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row([key], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// Upsert a raw value into the settings table
+fn put_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    let now = now_timestamp();
+    
+    conn.prepare_cached(
+        "INSERT INTO settings (key, value, updated_at)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET
+            value = excluded.value,
+            updated_at = excluded.updated_at",
+    )
+    .map_err(|e| e.to_string())?
+    .execute((key, value, &now))
+    .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+/// Read a setting and decode it as JSON into `T`
+/// Values are stored JSON-encoded (same as the frontend's uiState), so "true", "42", "{...}"
+fn get_setting_as<T: serde::de::DeserializeOwned>(conn: &Connection, key: &str) -> Result<Option<T>, String> {
+    match get_setting(conn, key)? {
+        None => Ok(None),
+        Some(raw) => serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|e| format!("Setting '{}' has unexpected value '{}': {}", key, raw, e)),
+    }
+}
+
+/// Maximum note content size in bytes (`limits.max_note_bytes` setting, default 25MB)
+fn max_note_bytes(conn: &Connection) -> Result<usize, String> {
+    Ok(get_setting(conn, "limits.max_note_bytes")?
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|bytes| *bytes > 0)
+        .unwrap_or(25 * 1024 * 1024))
+}
+
+/// Whether tag names are lowercased on save (`tags.lowercase` setting, off by default)
+fn lowercase_tags(conn: &Connection) -> Result<bool, String> {
+    Ok(get_setting(conn, "tags.lowercase")?.as_deref().map(str::trim) == Some("true"))
+}
+
+/// Canonical form of a tag name: trimmed, and lowercased when requested
+fn normalize_tag_name(name: &str, lowercase: bool) -> String {
+    let trimmed = name.trim();
+    if lowercase {
+        trimmed.to_lowercase()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Normalize a tag list for saving: canonical names, blanks dropped, duplicates collapsed (order kept)
+fn normalize_tags(conn: &Connection, tags: &[String]) -> Result<Vec<String>, String> {
+    let lowercase = lowercase_tags(conn)?;
+    let mut seen = HashSet::new();
+    Ok(tags
+        .iter()
+        .map(|tag| normalize_tag_name(tag, lowercase))
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.clone()))
+        .collect())
+}
+
+/// Record a permanent delete of a note/folder/tag for sync (call inside the delete's transaction)
+fn write_tombstone(conn: &Connection, entity_type: &str, entity_id: &str) -> Result<(), String> {
+    conn.prepare_cached(
+        "INSERT INTO tombstones (entity_type, entity_id, deleted_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(entity_type, entity_id) DO UPDATE SET deleted_at = excluded.deleted_at",
+    )
+    .map_err(|e| e.to_string())?
+    .execute((entity_type, entity_id, now_timestamp()))
+    .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+/// Drop tombstones older than `sync.tombstone_retention_days` (default 90)
+/// Peers that haven't synced within that window need a full resync anyway
+fn prune_tombstones(conn: &Connection) -> Result<usize, String> {
+    let retention_days = get_setting(conn, "sync.tombstone_retention_days")?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(90);
+    
+    conn.execute(
+        "DELETE FROM tombstones WHERE julianday(deleted_at) < julianday('now', ?1)",
+        [format!("-{} days", retention_days)],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Permanently delete trashed notes/folders older than `trash.retention_days`
+/// Missing or non-positive setting means no auto-purge. Returns the number of items purged
+fn purge_expired_trash(conn: &Connection) -> Result<usize, String> {
+    let retention_days = match get_setting(conn, "trash.retention_days")?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+    {
+        Some(days) if days > 0 => days,
+        _ => return Ok(0),
+    };
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(retention_days);
+    
+    // Compare parsed instants, not strings: JS (".000Z") and chrono ("+00:00") RFC3339
+    // variants don't sort correctly as text. Unparseable timestamps are never purged
+    let is_expired = |deleted_at: &str| {
+        chrono::DateTime::parse_from_rfc3339(deleted_at)
+            .map(|t| t < cutoff)
+            .unwrap_or(false)
+    };
+    
+    let collect_expired = |table: &str| -> Result<Vec<String>, String> {
+        let mut stmt = conn
+            .prepare(&format!("SELECT id, deleted_at FROM {} WHERE deleted_at IS NOT NULL", table))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<(String, String)>>>()
+            .map_err(|e| e.to_string())?;
+        Ok(rows
+            .into_iter()
+            .filter(|(_, deleted_at)| is_expired(deleted_at))
+            .map(|(id, _)| id)
+            .collect())
+    };
+    
+    let expired_notes = collect_expired("notes")?;
+    let expired_folders = collect_expired("folders")?;
+    if expired_notes.is_empty() && expired_folders.is_empty() {
+        return Ok(0);
+    }
+    
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    
+    // note_tags cascade automatically
+    for note_id in &expired_notes {
+        tx.execute("DELETE FROM notes WHERE id = ?1", [note_id])
+            .map_err(|e| e.to_string())?;
+        write_tombstone(&tx, "note", note_id)?;
+    }
+    
+    for folder_id in &expired_folders {
+        // Detach children first to avoid FK violations (same as delete_folder_permanently)
+        tx.execute("UPDATE folders SET parent_id = NULL WHERE parent_id = ?1", [folder_id])
+            .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM folder_delete_cascade WHERE root_folder_id = ?1", [folder_id])
+            .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM folders WHERE id = ?1", [folder_id])
+            .map_err(|e| e.to_string())?;
+        write_tombstone(&tx, "folder", folder_id)?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    Ok(expired_notes.len() + expired_folders.len())
+}
+
+/// Create (and populate) or drop the trigram `notes_fts_trigram` index and its sync triggers
+fn set_substring_index(conn: &Connection, enabled: bool) -> Result<(), String> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'notes_fts_trigram')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    
+    if enabled {
+        tx.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts_trigram USING fts5(
+                note_id UNINDEXED,
+                title,
+                content,
+                tokenize='trigram'
+            );
+            CREATE TRIGGER IF NOT EXISTS notes_fts_trigram_insert AFTER INSERT ON notes BEGIN
+                INSERT INTO notes_fts_trigram(note_id, title, content)
+                VALUES (new.id, new.title, new.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS notes_fts_trigram_update AFTER UPDATE ON notes BEGIN
+                UPDATE notes_fts_trigram
+                SET title = new.title, content = new.content
+                WHERE note_id = old.id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS notes_fts_trigram_delete AFTER DELETE ON notes BEGIN
+                DELETE FROM notes_fts_trigram WHERE note_id = old.id;
+            END;",
+        )
+        .map_err(|e| e.to_string())?;
+        if !exists {
+            println!("🔎 Building substring search index");
+            tx.execute(
+                "INSERT INTO notes_fts_trigram (note_id, title, content) SELECT id, title, content FROM notes",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    } else {
+        tx.execute_batch(
+            "DROP TRIGGER IF EXISTS notes_fts_trigram_insert;
+             DROP TRIGGER IF EXISTS notes_fts_trigram_update;
+             DROP TRIGGER IF EXISTS notes_fts_trigram_delete;
+             DROP TABLE IF EXISTS notes_fts_trigram;",
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    put_setting(&tx, "fts.substring_index", if enabled { "true" } else { "false" })?;
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+/// Whether the trigram substring index is maintained (see InitOptions::substring_index)
+fn substring_index_enabled(conn: &Connection) -> Result<bool, String> {
+    Ok(get_setting(conn, "fts.substring_index")?.as_deref() == Some("true"))
+}
+
+/// Schema version this build brings a database up to, stored in `PRAGMA user_version`
+/// Bump it with every schema change in init_database
+const SCHEMA_VERSION: i64 = 1;
+
+fn schema_version(conn: &Connection) -> Result<i64, String> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(|e| e.to_string())
+}
+
+/// What init_database opened. A `schema_version` above `app_schema_version` means the file
+/// was written by a newer build, which the app should warn about
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitResult {
+    pub path: String,
+    pub schema_version: i64,
+    pub app_schema_version: i64,
+    /// The file was below SCHEMA_VERSION (including brand-new) and has been brought up to it
+    pub migrated: bool,
+    pub read_only: bool,
+}
+
+/// Everything init_database does to a writable connection: pragmas, tables, migrations,
+/// indexes, FTS and startup housekeeping (separate so tests can run it on an in-memory database)
+fn configure_database(conn: &Connection, options: &InitOptions) -> Result<(), String> {
+    let journal_mode = options.journal_mode()?;
+    let synchronous = options.synchronous()?;
+    let cache_size_kb = options.cache_size_kb()?;
+    let foreign_keys = options.foreign_keys.unwrap_or(true);
+    let requested_tokenizer = options.fts_tokenizer()?;
+    let requested_autocheckpoint = options.wal_autocheckpoint()?;
+    let requested_substring_index = options.substring_index;
+    
+    // Hot paths (autosave, settings) use prepare_cached; give them room beyond rusqlite's default of 16
+    conn.set_prepared_statement_cache_capacity(64);
+    
+    // Enable foreign key constraints (critical for referential integrity)
+    conn.execute(&format!("PRAGMA foreign_keys = {}", if foreign_keys { "ON" } else { "OFF" }), [])
+        .map_err(|e| e.to_string())?;
+    
+    // Configure SQLite for optimal local-only performance (Apple Notes approach)
+    // WAL mode (default): Fast writes, concurrent reads
+    conn.query_row(&format!("PRAGMA journal_mode = {}", journal_mode), [], |_| Ok(())).ok();
+    // NORMAL synchronous (default): Balance between safety and speed (safe for local apps)
+    conn.execute(&format!("PRAGMA synchronous = {}", synchronous), []).ok();
+    // Page cache size in KB (negative value = KiB in SQLite), default ~8MB
+    conn.execute(&format!("PRAGMA cache_size = -{}", cache_size_kb), []).ok();
+    
+    // Create notes table (IF NOT EXISTS - preserves data on restart)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notes (
+            id TEXT PRIMARY KEY,
+            title TEXT NOT NULL,
+            description TEXT NOT NULL,
+            description_visible INTEGER NOT NULL,
+            emoji TEXT,
+            content TEXT NOT NULL,
+            tags_visible INTEGER NOT NULL,
+            is_favorite INTEGER NOT NULL,
+            folder_id TEXT,
+            daily_note_date TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            deleted_at TEXT,
+            sort_order REAL,
+            content_hash TEXT,
+            is_pinned INTEGER NOT NULL DEFAULT 0,
+            is_locked INTEGER NOT NULL DEFAULT 0,
+            locked_content BLOB,
+            lock_salt BLOB,
+            lock_nonce BLOB,
+            copied_from TEXT
         )",
         [],
     )
     .map_err(|e| e.to_string())?;
     
-    // Create settings table for user preferences
+    // Add sort_order column to existing notes table (migration)
+    // This will fail silently if the column already exists
+    let _ = conn.execute(
+        "ALTER TABLE notes ADD COLUMN sort_order REAL",
+        [],
+    );
+    
+    // Add content_hash column to existing notes table (migration)
+    let _ = conn.execute(
+        "ALTER TABLE notes ADD COLUMN content_hash TEXT",
+        [],
+    );
+    
+    // Add is_pinned column to existing notes table (migration)
+    let _ = conn.execute(
+        "ALTER TABLE notes ADD COLUMN is_pinned INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    
+    // Add per-note lock columns to existing notes table (migration)
+    let _ = conn.execute("ALTER TABLE notes ADD COLUMN is_locked INTEGER NOT NULL DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE notes ADD COLUMN locked_content BLOB", []);
+    let _ = conn.execute("ALTER TABLE notes ADD COLUMN lock_salt BLOB", []);
+    let _ = conn.execute("ALTER TABLE notes ADD COLUMN lock_nonce BLOB", []);
+    
+    // Add copied_from column to existing notes table (migration)
+    let _ = conn.execute("ALTER TABLE notes ADD COLUMN copied_from TEXT", []);
+    
+    // Create folders table
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS settings (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL,
-            updated_at TEXT NOT NULL
+        "CREATE TABLE IF NOT EXISTS folders (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            parent_id TEXT,
+            description TEXT NOT NULL,
+            description_visible INTEGER NOT NULL,
+            color TEXT,
+            emoji TEXT,
+            tags_visible INTEGER NOT NULL,
+            is_favorite INTEGER NOT NULL,
+            is_expanded INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            deleted_at TEXT,
+            FOREIGN KEY (parent_id) REFERENCES folders(id)
         )",
         [],
     )
     .map_err(|e| e.to_string())?;
     
-    // Create indexes for better performance (IF NOT EXISTS - safe for existing databases)
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_folder ON notes(folder_id)", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_daily_date ON notes(daily_note_date)", [])
-        .map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_deleted ON notes(deleted_at)", [])
+    // Create tags table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tags (
+            name TEXT PRIMARY KEY,
+            description TEXT NOT NULL,
+            description_visible INTEGER NOT NULL,
+            is_favorite INTEGER NOT NULL,
+            color TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            deleted_at TEXT
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Add deleted_at column to existing tags table (migration)
+    // This will fail silently if the column already exists
+    let _ = conn.execute(
+        "ALTER TABLE tags ADD COLUMN deleted_at TEXT",
+        [],
+    );
+    
+    // Create note_tags junction table (many-to-many)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS note_tags (
+            note_id TEXT NOT NULL,
+            tag_name TEXT NOT NULL,
+            PRIMARY KEY (note_id, tag_name),
+            FOREIGN KEY (note_id) REFERENCES notes(id) ON DELETE CASCADE,
+            FOREIGN KEY (tag_name) REFERENCES tags(name) ON DELETE CASCADE
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Create folder_tags junction table (many-to-many)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS folder_tags (
+            folder_id TEXT NOT NULL,
+            tag_name TEXT NOT NULL,
+            PRIMARY KEY (folder_id, tag_name),
+            FOREIGN KEY (folder_id) REFERENCES folders(id) ON DELETE CASCADE,
+            FOREIGN KEY (tag_name) REFERENCES tags(name) ON DELETE CASCADE
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Create settings table for user preferences
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Track which items a cascading folder soft-delete trashed, so restore_folder
+    // brings back exactly those (and not items that were already in the trash)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS folder_delete_cascade (
+            root_folder_id TEXT NOT NULL,
+            entity_type TEXT NOT NULL,
+            entity_id TEXT NOT NULL,
+            PRIMARY KEY (root_folder_id, entity_type, entity_id)
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Record permanent deletes so a sync peer can replay them (see get_tombstones_since)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tombstones (
+            entity_type TEXT NOT NULL,
+            entity_id TEXT NOT NULL,
+            deleted_at TEXT NOT NULL,
+            PRIMARY KEY (entity_type, entity_id)
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Opt-in save trail for diagnosing lost content (setting debug.audit_saves, see record_save_audit)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS save_audit (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            note_id TEXT NOT NULL,
+            old_length INTEGER,
+            new_length INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            saved_at TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_save_audit_note ON save_audit(note_id)", [])
+        .map_err(|e| e.to_string())?;
+    
+    // Note templates; default_tags is a JSON array of tag names
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS templates (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            content TEXT NOT NULL,
+            emoji TEXT,
+            default_tags TEXT NOT NULL DEFAULT '[]',
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Create indexes for better performance (IF NOT EXISTS - safe for existing databases)
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_folder ON notes(folder_id)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_daily_date ON notes(daily_note_date)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_deleted ON notes(deleted_at)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_favorite ON notes(is_favorite)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_updated ON notes(updated_at)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_sort_order ON notes(folder_id, sort_order)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_content_hash ON notes(content_hash)", [])
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_folders_parent ON folders(parent_id)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_folders_deleted ON folders(deleted_at)", [])
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_note_tags_note ON note_tags(note_id)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_note_tags_tag ON note_tags(tag_name)", [])
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_folder_tags_folder ON folder_tags(folder_id)", [])
+        .map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_folder_tags_tag ON folder_tags(tag_name)", [])
+        .map_err(|e| e.to_string())?;
+    
+    // Create FTS5 virtual table for full-text search (Apple Notes / Bear approach)
+    // Databases created before fts.tokenizer was tracked use unicode61
+    let fts_exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'notes_fts')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let stored_tokenizer = get_setting(conn, "fts.tokenizer")?.unwrap_or_else(|| "unicode61".to_string());
+    let tokenizer = requested_tokenizer.map(str::to_string).unwrap_or_else(|| stored_tokenizer.clone());
+    
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    
+    if fts_exists && tokenizer != stored_tokenizer {
+        println!("🔎 Rebuilding search index: {} -> {}", stored_tokenizer, tokenizer);
+        tx.execute("DROP TABLE notes_fts", []).map_err(|e| e.to_string())?;
+    }
+    
+    tx.execute(
+        &format!(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(
+                note_id UNINDEXED,
+                title,
+                content,
+                tokenize='{}'
+            )",
+            tokenizer
+        ),
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // A freshly (re)created index must be repopulated from the notes table
+    if !fts_exists || tokenizer != stored_tokenizer {
+        tx.execute(
+            "INSERT INTO notes_fts (note_id, title, content) SELECT id, title, content FROM notes",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    
+    put_setting(&tx, "fts.tokenizer", &tokenizer)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    // Triggers to keep FTS in sync with notes table
+    // Insert trigger
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS notes_fts_insert AFTER INSERT ON notes BEGIN
+            INSERT INTO notes_fts(note_id, title, content)
+            VALUES (new.id, new.title, new.content);
+        END",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Update trigger
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS notes_fts_update AFTER UPDATE ON notes BEGIN
+            UPDATE notes_fts 
+            SET title = new.title, content = new.content
+            WHERE note_id = old.id;
+        END",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Delete trigger
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS notes_fts_delete AFTER DELETE ON notes BEGIN
+            DELETE FROM notes_fts WHERE note_id = old.id;
+        END",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Optional substring index: an explicit option wins, otherwise keep what the database has
+    let substring_index = match requested_substring_index {
+        Some(enabled) => enabled,
+        None => substring_index_enabled(conn)?,
+    };
+    set_substring_index(conn, substring_index)?;
+    
+    // WAL autocheckpoint threshold: an explicit option wins and is remembered for later launches
+    if let Some(pages) = requested_autocheckpoint {
+        put_setting(conn, "wal.autocheckpoint_pages", &pages.to_string())?;
+    }
+    let autocheckpoint_pages = get_setting(conn, "wal.autocheckpoint_pages")?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|pages| *pages >= 0);
+    if let Some(pages) = autocheckpoint_pages {
+        conn.query_row(&format!("PRAGMA wal_autocheckpoint = {}", pages), [], |_| Ok(()))
+            .map_err(|e| e.to_string())?;
+    }
+    
+    // Backfill content hashes for rows saved before the column existed
+    backfill_content_hashes(conn)?;
+    // Rewrite sync-cursor timestamps saved before writes were normalized
+    backfill_canonical_timestamps(conn)?;
+    
+    // 🧹 Auto-empty trash per the user's retention setting (no-op when unset)
+    let purged = purge_expired_trash(conn)?;
+    if purged > 0 {
+        println!("🧹 Purged {} expired items from trash", purged);
+    }
+    
+    Ok(())
+}
+
+/// Initialize database at the specified path
+#[tauri::command]
+pub fn init_database(
+    db_path: String,
+    options: Option<InitOptions>,
+    passphrase: Option<String>,
+    state: State<DbConnection>,
+) -> Result<InitResult, String> {
+    // Validate options up front so a typo never half-configures the connection
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    let read_only = options.read_only.unwrap_or(false);
+    
+    let conn = if read_only {
+        // 🛟 Safe mode: never create, migrate or checkpoint a possibly-corrupt file
+        Connection::open_with_flags(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| e.to_string())?
+    } else {
+        // Fresh installs: make sure the app data directory exists before SQLite opens the file
+        prepare_db_path(&db_path)?;
+        Connection::open(&db_path).map_err(|e| e.to_string())?
+    };
+    
+    // 🔐 Encryption at rest (SQLCipher): the key must be set before anything else touches the file
+    if let Some(passphrase) = passphrase.as_deref() {
+        apply_passphrase(&conn, passphrase)?;
+    }
+    
+    let stored_version = schema_version(&conn)?;
+    
+    if read_only {
+        println!("🛟 Opened database read-only: {}", db_path);
+        *state.0.lock().unwrap() = Some(conn);
+        return Ok(InitResult {
+            path: db_path,
+            schema_version: stored_version,
+            app_schema_version: SCHEMA_VERSION,
+            migrated: false,
+            read_only: true,
+        });
+    }
+    
+    configure_database(&conn, &options)?;
+    
+    // Record the schema version; a newer database is left as-is so its build can still open it
+    let migrated = stored_version < SCHEMA_VERSION;
+    if migrated {
+        conn.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+            .map_err(|e| e.to_string())?;
+    } else if stored_version > SCHEMA_VERSION {
+        println!(
+            "⚠️ Database schema v{} is newer than this build (v{})",
+            stored_version, SCHEMA_VERSION
+        );
+    }
+    
+    // Store connection in state
+    *state.0.lock().unwrap() = Some(conn);
+    
+    println!("🗄️ Database initialized at: {} (schema v{})", db_path, SCHEMA_VERSION);
+    Ok(InitResult {
+        path: db_path,
+        schema_version: stored_version.max(SCHEMA_VERSION),
+        app_schema_version: SCHEMA_VERSION,
+        migrated,
+        read_only: false,
+    })
+}
+
+/// Schema version of the open database (`PRAGMA user_version`, see SCHEMA_VERSION)
+#[tauri::command]
+pub fn get_schema_version(state: State<DbConnection>) -> Result<i64, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    schema_version(conn)
+}
+
+/// Canonical stored timestamp: UTC, millisecond precision, `Z` suffix (same as JS `toISOString()`)
+/// One format everywhere keeps text comparisons and ORDER BY on timestamp columns correct
+fn format_timestamp(dt: chrono::DateTime<chrono::Utc>) -> String {
+    dt.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Current time in the canonical stored format
+fn now_timestamp() -> String {
+    format_timestamp(chrono::Utc::now())
+}
+
+/// Parse an incoming RFC3339 timestamp and re-format it canonically
+fn normalize_timestamp(field: &str, value: &str) -> Result<String, DbError> {
+    chrono::DateTime::parse_from_rfc3339(value.trim())
+        .map(|dt| format_timestamp(dt.with_timezone(&chrono::Utc)))
+        .map_err(|_| DbError::Validation(format!("Invalid {} '{}': expected an RFC3339 timestamp", field, value)))
+}
+
+/// A date-range bound: an RFC3339 timestamp, or a plain YYYY-MM-DD day meaning its first
+/// (or, for an end bound, last) millisecond in UTC
+fn normalize_range_bound(field: &str, value: &str, end_of_day: bool) -> Result<String, DbError> {
+    match chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+        Ok(day) => {
+            let time = if end_of_day { "23:59:59.999" } else { "00:00:00.000" };
+            Ok(format!("{}T{}Z", day.format("%Y-%m-%d"), time))
+        }
+        Err(_) => normalize_timestamp(field, value),
+    }
+}
+
+/// Like normalize_timestamp for optional columns; None and "" both store NULL
+fn normalize_optional_timestamp(field: &str, value: Option<&str>) -> Result<Option<String>, DbError> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => normalize_timestamp(field, value).map(Some),
+    }
+}
+
+/// Daily note dates are plain calendar days (YYYY-MM-DD); "" stores NULL
+fn normalize_daily_note_date(value: Option<&str>) -> Result<Option<String>, DbError> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|day| Some(day.format("%Y-%m-%d").to_string()))
+            .map_err(|_| DbError::Validation(format!("Invalid dailyNoteDate '{}': expected YYYY-MM-DD", value))),
+    }
+}
+
+/// Whether a code point is pictographic enough to start an emoji (or is a regional indicator)
+/// Coarse block ranges - good enough to reject words while accepting anything an emoji picker emits
+fn is_emoji_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF   // pictographs, emoticons, transport, regional indicators, ...
+            | 0x2600..=0x27BF // misc symbols, dingbats
+            | 0x2300..=0x23FF // misc technical (⌚ ⏰)
+            | 0x2190..=0x21FF // arrows
+            | 0x25A0..=0x25FF // geometric shapes (▶ ◀)
+            | 0x2B00..=0x2BFF // misc symbols and arrows (⭐ ⬆)
+            | 0x2934 | 0x2935 | 0x203C | 0x2049 | 0x2122 | 0x2139
+            | 0x3030 | 0x303D | 0x3297 | 0x3299 | 0x00A9 | 0x00AE
+    )
+}
+
+/// Validate an `emoji` field: one grapheme cluster that is actually an emoji
+/// Flags, ZWJ sequences, skin tones and keycaps are single clusters; "" is coerced to None
+fn normalize_emoji(emoji: Option<&str>) -> Result<Option<String>, DbError> {
+    let emoji = match emoji.map(str::trim) {
+        None | Some("") => return Ok(None),
+        Some(emoji) => emoji,
+    };
+    
+    let mut graphemes = emoji.graphemes(true);
+    let is_single_cluster = graphemes.next().is_some() && graphemes.next().is_none();
+    // Keycaps (1️⃣ #️⃣) start with an ASCII character but end with U+20E3
+    let is_emoji = emoji.chars().next().is_some_and(is_emoji_char) || emoji.ends_with('\u{20E3}');
+    
+    if !is_single_cluster || !is_emoji {
+        return Err(DbError::Validation(format!("Invalid emoji '{}': expected a single emoji", emoji)));
+    }
+    
+    Ok(Some(emoji.to_string()))
+}
+
+/// Stable SHA-256 (hex) of note content, used for change detection and dedup
+/// Empty content hashes to "" so blank notes never count as duplicates
+fn content_hash(content: &str) -> String {
+    if content.is_empty() {
+        return String::new();
+    }
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Compute content_hash for any rows that don't have one yet (one-time migration)
+fn backfill_content_hashes(conn: &Connection) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT id, content FROM notes WHERE content_hash IS NULL")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(String, String)>>>()
+        .map_err(|e| e.to_string())?;
+    
+    if rows.is_empty() {
+        return Ok(());
+    }
+    
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    {
+        let mut update = tx
+            .prepare("UPDATE notes SET content_hash = ?1 WHERE id = ?2")
+            .map_err(|e| e.to_string())?;
+        for (id, content) in &rows {
+            update.execute((content_hash(content), id)).map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🔑 Backfilled content hashes for {} notes", rows.len());
+    Ok(())
+}
+
+/// GLOB matching the shape format_timestamp produces (e.g. 2024-03-01T12:30:00.000Z)
+const CANONICAL_TIMESTAMP_GLOB: &str =
+    "[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9].[0-9][0-9][0-9]Z";
+
+/// Older builds stored whatever the caller sent ("+00:00", offsets, no millis). Rewrite those once
+/// to the canonical UTC form so modified-since cursors compare as plain text and can use the index.
+/// Values SQLite can't parse are left alone
+fn backfill_canonical_timestamps(conn: &Connection) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let mut rewritten = 0;
+    for (table, column) in [
+        ("notes", "updated_at"),
+        ("folders", "updated_at"),
+        ("tags", "updated_at"),
+        ("tombstones", "deleted_at"),
+    ] {
+        rewritten += tx
+            .execute(
+                &format!(
+                    "UPDATE {0} SET {1} = strftime('%Y-%m-%dT%H:%M:%fZ', {1})
+                     WHERE {1} NOT GLOB ?1 AND strftime('%Y-%m-%dT%H:%M:%fZ', {1}) IS NOT NULL",
+                    table, column
+                ),
+                [CANONICAL_TIMESTAMP_GLOB],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    if rewritten > 0 {
+        println!("🕒 Canonicalized {} legacy timestamps", rewritten);
+    }
+    Ok(())
+}
+
+/// Checks shared by every write of note content: the size limit and the boot-state guard
+fn guard_note_content(conn: &Connection, note_id: &str, label: &str, content: &str) -> Result<(), String> {
+    // 📏 Refuse runaway pastes before they hit the database
+    let allowed = max_note_bytes(conn)?;
+    if content.len() > allowed {
+        return Err(DbError::TooLarge { actual: content.len(), allowed }.into());
+    }
+    
+    // 🛡️ GUARD: Only prevent PURE boot state (null, empty string, etc.)
+    // Allow structured empty content (intentional deletions)
+    let is_pure_boot_state = content.is_empty() 
+        || content == r#""""# 
+        || content == "{}";
+    
+    if is_pure_boot_state {
+        // Check if note exists in DB with content
+        let existing_content_len: Option<usize> = conn
+            .prepare_cached("SELECT LENGTH(content) FROM notes WHERE id = ?1")
+            .and_then(|mut stmt| stmt.query_row([note_id], |row| row.get(0)))
+            .ok();
+        
+        // Only block if overwriting existing content with pure boot state
+        if let Some(existing_len) = existing_content_len {
+            if existing_len > 200 {
+                return Err(format!(
+                    "🚨 BLOCKED: Attempted to overwrite note '{}' ({} chars) with pure boot state",
+                    label, existing_len
+                ));
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Stored content length captured before a save while debug.audit_saves = "true"
+struct PendingSaveAudit {
+    old_length: Option<i64>,
+}
+
+/// Read the stored content length ahead of a write; None when the save trail is off
+fn begin_save_audit(conn: &Connection, note_id: &str) -> Result<Option<PendingSaveAudit>, String> {
+    if get_setting(conn, "debug.audit_saves")?.as_deref().map(str::trim) != Some("true") {
+        return Ok(None);
+    }
+    
+    let old_length: Option<i64> = conn
+        .prepare_cached("SELECT LENGTH(CAST(content AS BLOB)) FROM notes WHERE id = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row([note_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(Some(PendingSaveAudit { old_length }))
+}
+
+/// Log the old vs new content length of a save that succeeded (call after the write)
+/// Nothing is recorded unless the stored content now is `new_content`, so a locked note that
+/// kept its old content doesn't show up as saved
+fn record_save_audit(
+    conn: &Connection,
+    pending: Option<PendingSaveAudit>,
+    source: &str,
+    note_id: &str,
+    new_content: &str,
+) -> Result<(), String> {
+    let pending = match pending {
+        Some(pending) => pending,
+        None => return Ok(()),
+    };
+    
+    let stored_hash: Option<Option<String>> = conn
+        .prepare_cached("SELECT content_hash FROM notes WHERE id = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row([note_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if stored_hash.flatten() != Some(content_hash(new_content)) {
+        return Ok(());
+    }
+    
+    conn.prepare_cached(
+        "INSERT INTO save_audit (note_id, old_length, new_length, source, saved_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )
+    .map_err(|e| e.to_string())?
+    .execute((note_id, pending.old_length, new_content.len() as i64, source, now_timestamp()))
+    .map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+/// Upsert a note and its tag relationships (shared by save_note and save_notes_batch)
+/// Statements are cached so batch callers reuse them across the loop
+fn write_note(conn: &Connection, note: &Note, server_timestamps: bool) -> Result<(), String> {
+    guard_note_content(conn, &note.id, &note.title, &note.content)?;
+    
+    let emoji = normalize_emoji(note.emoji.as_deref())?;
+    let created_at = normalize_timestamp("createdAt", &note.created_at)?;
+    let deleted_at = normalize_optional_timestamp("deletedAt", note.deleted_at.as_deref())?;
+    let daily_note_date = normalize_daily_note_date(note.daily_note_date.as_deref())?;
+    
+    // 🕒 Server-side timestamps: ignore the caller's updated_at and guard against regressions
+    let updated_at = if server_timestamps {
+        now_timestamp()
+    } else {
+        normalize_timestamp("updatedAt", &note.updated_at)?
+    };
+    let updated_at_clause = if server_timestamps {
+        "CASE WHEN excluded.updated_at > notes.updated_at THEN excluded.updated_at ELSE notes.updated_at END"
+    } else {
+        "excluded.updated_at"
+    };
+    
+    // ✅ UPSERT: Use INSERT ... ON CONFLICT instead of INSERT OR REPLACE
+    // This preserves row identity and is safer (created_at is never touched on update)
+    let mut upsert = conn
+        .prepare_cached(&format!(
+            "INSERT INTO notes 
+            (id, title, description, description_visible, emoji, content, tags_visible, is_favorite, 
+             folder_id, daily_note_date, created_at, updated_at, deleted_at, content_hash)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+            ON CONFLICT(id) DO UPDATE SET
+                title = excluded.title,
+                description = excluded.description,
+                description_visible = excluded.description_visible,
+                emoji = excluded.emoji,
+                content = CASE WHEN notes.is_locked = 1 THEN notes.content ELSE excluded.content END,
+                content_hash = CASE WHEN notes.is_locked = 1 THEN notes.content_hash ELSE excluded.content_hash END,
+                tags_visible = excluded.tags_visible,
+                is_favorite = excluded.is_favorite,
+                folder_id = excluded.folder_id,
+                daily_note_date = excluded.daily_note_date,
+                updated_at = {},
+                deleted_at = excluded.deleted_at",
+            updated_at_clause
+        ))
+        .map_err(|e| e.to_string())?;
+    upsert
+        .execute((
+            &note.id,
+            &note.title,
+            &note.description,
+            note.description_visible as i32,
+            &emoji,
+            &note.content,
+            note.tags_visible as i32,
+            note.is_favorite as i32,
+            &note.folder_id,
+            &daily_note_date,
+            &created_at,
+            &updated_at,
+            &deleted_at,
+            content_hash(&note.content),
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    // Canonical tag names so "Work" / " work " don't become separate tags
+    let tags = normalize_tags(conn, &note.tags)?;
+    
+    // Ensure all tags exist in tags table (idempotent upsert)
+    // This prevents FK violations when inserting into note_tags
+    let mut ensure_tag = conn
+        .prepare_cached(
+            "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at)
+             VALUES (?1, '', 1, 0, NULL, ?2, ?2)
+             ON CONFLICT(name) DO NOTHING",
+        )
+        .map_err(|e| e.to_string())?;
+    for tag in &tags {
+        ensure_tag.execute((tag, &updated_at)).map_err(|e| e.to_string())?;
+    }
+    
+    // Delete existing tag relationships
+    conn.prepare_cached("DELETE FROM note_tags WHERE note_id = ?1")
+        .map_err(|e| e.to_string())?
+        .execute([&note.id])
+        .map_err(|e| e.to_string())?;
+    
+    // Insert new tag relationships
+    let mut insert_tag = conn
+        .prepare_cached("INSERT INTO note_tags (note_id, tag_name) VALUES (?1, ?2)")
+        .map_err(|e| e.to_string())?;
+    for tag in &tags {
+        insert_tag.execute((&note.id, tag)).map_err(|e| e.to_string())?;
+    }
+    
+    Ok(())
+}
+
+/// Save or update a note
+/// With `server_timestamps`, updated_at is stamped here (and never moves backwards)
+/// instead of trusting the caller's clock; created_at is only honored on insert
+#[tauri::command]
+pub fn save_note(
+    note: Note,
+    server_timestamps: Option<bool>,
+    state: State<DbConnection>,
+    history: State<DeleteHistory>,
+) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    // 🔍 DEBUG: Log content length to catch empty saves
+    println!(
+        "💾 Saving note {} | title: {} | content length: {}",
+        truncate_chars(&note.id, 20),
+        truncate_chars(&note.title, 30),
+        note.content.len()
+    );
+    
+    let audit = begin_save_audit(conn, &note.id)?;
+    // The frontend trashes a note by saving it with deletedAt set; remember that for undo_last_delete
+    let was_active = note.deleted_at.is_some() && trash_state(conn, "notes", &note.id)? == Some(None);
+    write_note(conn, &note, server_timestamps.unwrap_or(false))?;
+    record_save_audit(conn, audit, "save_note", &note.id, &note.content)?;
+    if was_active {
+        if let Some(Some(deleted_at)) = trash_state(conn, "notes", &note.id)? {
+            history.push("note", &note.id, deleted_at);
+        }
+    }
+    
+    Ok(format!("Note saved: {}", note.id))
+}
+
+/// Autosave fast path: rewrite only content (+ hash) and updated_at, leaving metadata and tags alone
+/// Same size / boot-state guards as save_note; the FTS update trigger keeps search in sync
+#[tauri::command]
+pub fn update_note_content(
+    note_id: String,
+    content: String,
+    updated_at: String,
+    state: State<DbConnection>,
+) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    guard_note_content(conn, &note_id, &note_id, &content)?;
+    let updated_at = normalize_timestamp("updatedAt", &updated_at)?;
+    let audit = begin_save_audit(conn, &note_id)?;
+    
+    let updated = conn
+        .prepare_cached(
+            "UPDATE notes SET content = ?1, content_hash = ?2, updated_at = ?3
+             WHERE id = ?4 AND is_locked = 0",
+        )
+        .map_err(|e| e.to_string())?
+        .execute((&content, content_hash(&content), &updated_at, &note_id))
+        .map_err(|e| e.to_string())?;
+    
+    if updated == 0 {
+        return Err(format!("Note '{}' not found or locked", note_id));
+    }
+    record_save_audit(conn, audit, "update_note_content", &note_id, &content)?;
+    
+    Ok(format!("Note content updated: {}", note_id))
+}
+
+/// The save trail recorded for a note while debug.audit_saves was on, newest first
+#[tauri::command]
+pub fn get_save_audit(note_id: String, state: State<DbConnection>) -> Result<Vec<SaveAuditEntry>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(
+            "SELECT old_length, new_length, source, saved_at FROM save_audit
+             WHERE note_id = ?1
+             ORDER BY id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let entries = stmt
+        .query_map([&note_id], |row| {
+            Ok(SaveAuditEntry {
+                old_length: row.get(0)?,
+                new_length: row.get(1)?,
+                source: row.get(2)?,
+                saved_at: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<SaveAuditEntry>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(entries)
+}
+
+/// Apply `rewrite` to each text node of a TipTap document, depth-first; returns the total hit count
+/// Without `all`, stops after the first node with a hit. Matches never span two text nodes
+/// (a bold word splits a sentence into separate nodes)
+fn rewrite_doc_text(
+    node: &mut serde_json::Value,
+    rewrite: &mut impl FnMut(&mut String) -> usize,
+    all: bool,
+) -> usize {
+    if let Some(serde_json::Value::String(text)) = node.get_mut("text") {
+        return rewrite(text);
+    }
+    let mut hits = 0;
+    if let Some(children) = node.get_mut("content").and_then(|c| c.as_array_mut()) {
+        for child in children {
+            hits += rewrite_doc_text(child, rewrite, all);
+            if hits > 0 && !all {
+                break;
+            }
+        }
+    }
+    hits
+}
+
+/// Apply `rewrite` to a note's content: text nodes only for a TipTap document, the whole
+/// string otherwise. Returns the new content and hit count
+fn rewrite_note_text(content: &str, mut rewrite: impl FnMut(&mut String) -> usize, all: bool) -> (String, usize) {
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(mut doc) if doc.is_object() => {
+            let hits = rewrite_doc_text(&mut doc, &mut rewrite, all);
+            (doc.to_string(), hits)
+        }
+        _ => {
+            let mut text = content.to_string();
+            let hits = rewrite(&mut text);
+            (text, hits)
+        }
+    }
+}
+
+/// Server-side find-and-replace in one note (first match, or every match with `all`)
+/// Only document text is touched, never the JSON structure; returns the number of replacements
+#[tauri::command]
+pub fn replace_in_note(
+    note_id: String,
+    find: String,
+    replace: String,
+    all: bool,
+    state: State<DbConnection>,
+) -> Result<usize, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    if find.is_empty() {
+        return Err(DbError::Validation("Search text cannot be empty".to_string()).into());
+    }
+    
+    let (content, is_locked): (String, bool) = conn
+        .prepare_cached("SELECT content, is_locked FROM notes WHERE id = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row([&note_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Note '{}' not found", note_id))?;
+    if is_locked {
+        return Err(format!("Note '{}' is locked; unlock it before editing", note_id));
+    }
+    
+    let (replaced, hits) = rewrite_note_text(
+        &content,
+        |text| {
+            let hits = if all { text.matches(find.as_str()).count() } else { text.contains(&find) as usize };
+            if hits > 0 {
+                *text = if all { text.replace(&find, &replace) } else { text.replacen(&find, &replace, 1) };
+            }
+            hits
+        },
+        all,
+    );
+    if hits == 0 {
+        return Ok(0);
+    }
+    
+    guard_note_content(conn, &note_id, &note_id, &replaced)?;
+    let audit = begin_save_audit(conn, &note_id)?;
+    
+    conn.prepare_cached("UPDATE notes SET content = ?1, content_hash = ?2, updated_at = ?3 WHERE id = ?4")
+        .map_err(|e| e.to_string())?
+        .execute((&replaced, content_hash(&replaced), now_timestamp(), &note_id))
+        .map_err(|e| e.to_string())?;
+    record_save_audit(conn, audit, "replace_in_note", &note_id, &replaced)?;
+    
+    println!("🔁 Replaced {} occurrence(s) in note {}", hits, note_id);
+    Ok(hits)
+}
+
+/// Load a single note by ID
+#[tauri::command]
+pub fn load_note(note_id: String, state: State<DbConnection>) -> Result<Note, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // Load note
+    let mut note = conn
+        .prepare_cached(&format!("SELECT {} FROM notes WHERE id = ?1", NOTE_COLUMNS))
+        .map_err(|e| e.to_string())?
+        .query_row([&note_id], note_from_row)
+        .map_err(|e| e.to_string())?;
+    
+    // Load tags
+    let mut stmt = conn
+        .prepare_cached("SELECT tag_name FROM note_tags WHERE note_id = ?1")
+        .map_err(|e| e.to_string())?;
+    
+    let tags = stmt
+        .query_map([&note_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>>>()
+        .map_err(|e| e.to_string())?;
+    
+    note.tags = tags;
+    
+    Ok(note)
+}
+
+/// Length of a note's content in characters, for planning load_note_content_chunk calls
+#[tauri::command]
+pub fn get_note_content_length(note_id: String, state: State<DbConnection>) -> Result<i64, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    conn.query_row("SELECT LENGTH(content) FROM notes WHERE id = ?1", [&note_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Note '{}' not found", note_id))
+}
+
+/// Load part of a very large note's content so the editor can fill it in progressively
+/// `offset` is 1-BASED and both values count characters (SQLite `substr` semantics):
+/// offset 1 + length N is the first chunk, then offset N+1, and so on until get_note_content_length
+#[tauri::command]
+pub fn load_note_content_chunk(
+    note_id: String,
+    offset: i64,
+    length: i64,
+    state: State<DbConnection>,
+) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // substr() treats 0 and negative offsets specially - reject them rather than return surprising slices
+    if offset < 1 {
+        return Err(format!("Invalid offset {}: offsets are 1-based", offset));
+    }
+    if length < 0 {
+        return Err(format!("Invalid length {}: must not be negative", length));
+    }
+    
+    let chunk: Option<String> = conn
+        .prepare_cached("SELECT substr(content, ?2, ?3) FROM notes WHERE id = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row((&note_id, offset, length), |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    
+    chunk.ok_or_else(|| format!("Note '{}' not found", note_id))
+}
+
+/// 0-based char offsets of every match of `needle` in a note's stored content
+/// (add 1 for load_note_content_chunk); matches don't overlap - scanning resumes after each hit
+#[tauri::command]
+pub fn search_within_note(
+    note_id: String,
+    needle: String,
+    case_sensitive: bool,
+    state: State<DbConnection>,
+) -> Result<Vec<usize>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let content: String = conn
+        .prepare_cached("SELECT content FROM notes WHERE id = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row([&note_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Note '{}' not found", note_id))?;
+    
+    let haystack: Vec<char> = content.chars().collect();
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return Ok(Vec::new());
+    }
+    
+    // Compare per char so case folding can never shift the offsets
+    let chars_match = |a: char, b: char| {
+        a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()))
+    };
+    
+    let mut positions = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= haystack.len() {
+        if haystack[i..i + needle.len()].iter().zip(&needle).all(|(&a, &b)| chars_match(a, b)) {
+            positions.push(i);
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    
+    Ok(positions)
+}
+
+/// Load all notes (pinned notes first)
+/// `sort_by`: "updated" (default), "created", "title", "favorite" or "manual" (drag-to-reorder order)
+/// `sort_direction`: "asc" / "desc" (default: "asc" for title and manual, "desc" otherwise)
+/// `include_deleted`: defaults to true (frontend filters trash); false skips soft-deleted notes
+#[tauri::command]
+pub fn load_all_notes(
+    sort_by: Option<String>,
+    sort_direction: Option<String>,
+    include_deleted: Option<bool>,
+    state: State<DbConnection>,
+) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // Column names can't be bound as parameters - only allowlisted SQL is ever interpolated
+    let (sort_column, default_direction) = match sort_by.as_deref() {
+        None | Some("updated") => ("updated_at", "DESC"),
+        Some("created") => ("created_at", "DESC"),
+        Some("title") => ("title COLLATE NOCASE", "ASC"),
+        Some("favorite") => ("is_favorite", "DESC"),
+        // NULL sort_order sorts first, so never-reordered (e.g. new) notes stay recency-ordered on top
+        Some("manual") => ("sort_order", "ASC"),
+        Some(other) => return Err(format!("Invalid sort_by '{}'", other)),
+    };
+    let direction = match sort_direction.as_deref().map(|d| d.to_lowercase()).as_deref() {
+        None => default_direction,
+        Some("asc") => "ASC",
+        Some("desc") => "DESC",
+        Some(other) => return Err(format!("Invalid sort_direction '{}': expected asc or desc", other)),
+    };
+    let where_clause = if include_deleted.unwrap_or(true) { "" } else { "WHERE deleted_at IS NULL" };
+    
+    // Most recent edit breaks ties, unless that's already the sort
+    let tiebreak = if sort_column == "updated_at" { "" } else { ", updated_at DESC" };
+    
+    // No checkpoint needed! Same connection automatically sees WAL writes
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM notes {} ORDER BY is_pinned DESC, {} {}{}",
+            NOTE_COLUMNS, where_clause, sort_column, direction, tiebreak
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map([], note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    // Load ALL tags in ONE query (Apple Notes approach - no N+1!)
+    let mut tags_by_note = load_tags_by_note(conn, None)?;
+    
+    // Assign tags to notes (O(1) lookup per note)
+    for note in &mut notes {
+        note.tags = tags_by_note.remove(&note.id).unwrap_or_default();
+    }
+    
+    Ok(notes)
+}
+
+/// Position after the last note of a load_folder_notes_after page
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteCursor {
+    pub updated_at: String,
+    pub id: String,
+}
+
+/// One page of load_folder_notes_after; `next_cursor` is None on the last page
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotePage {
+    pub notes: Vec<Note>,
+    pub next_cursor: Option<NoteCursor>,
+}
+
+/// Keyset-paged active notes of one folder (root when None), newest edit first
+/// Pass the previous page's next_cursor as `after_updated_at` / `after_id` (both None for the
+/// first page). Unlike OFFSET, edits between calls never skip or repeat a note
+/// updated_at compares as canonical text (see MODIFIED_SINCE_CLAUSE), with id breaking ties
+#[tauri::command]
+pub fn load_folder_notes_after(
+    folder_id: Option<String>,
+    after_updated_at: Option<String>,
+    after_id: Option<String>,
+    limit: i64,
+    state: State<DbConnection>,
+) -> Result<NotePage, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    if limit <= 0 {
+        return Err(DbError::Validation(format!("Invalid limit {}: must be positive", limit)).into());
+    }
+    let (after_updated_at, after_id) = match (after_updated_at, after_id) {
+        (Some(updated_at), Some(id)) => (Some(normalize_timestamp("after_updated_at", &updated_at)?), Some(id)),
+        (None, None) => (None, None),
+        _ => {
+            return Err(DbError::Validation(
+                "after_updated_at and after_id must be passed together".to_string(),
+            )
+            .into())
+        }
+    };
+    
+    // One extra row tells us whether another page follows
+    let mut stmt = conn
+        .prepare_cached(&format!(
+            "SELECT {} FROM notes
+             WHERE deleted_at IS NULL AND folder_id IS ?1
+               AND (?2 IS NULL OR (updated_at, id) < (?2, ?3))
+             ORDER BY updated_at DESC, id DESC
+             LIMIT ?4",
+            NOTE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut notes: Vec<Note> = stmt
+        .query_map((&folder_id, &after_updated_at, &after_id, limit + 1), note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    let next_cursor = if notes.len() as i64 > limit {
+        notes.truncate(limit as usize);
+        notes.last().map(|last| NoteCursor { updated_at: last.updated_at.clone(), id: last.id.clone() })
+    } else {
+        None
+    };
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(NotePage { notes, next_cursor })
+}
+
+/// Set a note's manual position within its folder
+/// REAL ordering lets the frontend insert between two notes with a midpoint value
+#[tauri::command]
+pub fn reorder_note(note_id: String, new_order: f64, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    if !new_order.is_finite() {
+        return Err(format!("Invalid sort order: {}", new_order));
+    }
+    
+    let updated = conn
+        .execute(
+            "UPDATE notes SET sort_order = ?1 WHERE id = ?2",
+            (new_order, &note_id),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    if updated == 0 {
+        return Err(format!("Note '{}' not found", note_id));
+    }
+    
+    Ok(format!("Note reordered: {}", note_id))
+}
+
+/// Drag-and-drop in one step: move a note into `folder_id` (root when None) at `sort_order`
+/// A single UPDATE, so no other write can see the note moved but not yet positioned;
+/// updated_at is bumped only when the folder actually changes (like reorder_note, position alone doesn't)
+#[tauri::command]
+pub fn place_note(
+    note_id: String,
+    folder_id: Option<String>,
+    sort_order: f64,
+    state: State<DbConnection>,
+) -> Result<Note, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    if !sort_order.is_finite() {
+        return Err(format!("Invalid sort order: {}", sort_order));
+    }
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    if let Some(target) = &folder_id {
+        let target_deleted_at: Option<Option<String>> = tx
+            .query_row("SELECT deleted_at FROM folders WHERE id = ?1", [target], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        match target_deleted_at {
+            None => return Err(format!("Target folder '{}' not found", target)),
+            Some(Some(_)) => return Err(format!("Target folder '{}' is in the trash", target)),
+            Some(None) => {}
+        }
+    }
+    
+    let updated = tx
+        .execute(
+            "UPDATE notes SET
+                updated_at = CASE WHEN folder_id IS ?1 THEN updated_at ELSE ?2 END,
+                folder_id = ?1,
+                sort_order = ?3
+             WHERE id = ?4",
+            (&folder_id, now_timestamp(), sort_order, &note_id),
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Note '{}' not found", note_id));
+    }
+    
+    let note = tx
+        .query_row(&format!("SELECT {} FROM notes WHERE id = ?1", NOTE_COLUMNS), [&note_id], note_from_row)
+        .map_err(|e| e.to_string())?;
+    let mut notes = [note];
+    populate_note_tags(&tx, &mut notes)?;
+    let [note] = notes;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("📌 Placed note {} in {:?} at {}", note_id, note.folder_id, sort_order);
+    Ok(note)
+}
+
+/// Bump a note's updated_at to now without touching its content ("mark as reviewed")
+/// Returns the new timestamp so the caller can update its cache
+#[tauri::command]
+pub fn touch_note(note_id: String, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let now = now_timestamp();
+    let updated = conn
+        .execute(
+            "UPDATE notes SET updated_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            (&now, &note_id),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    if updated == 0 {
+        return Err(format!("Active note '{}' not found", note_id));
+    }
+    
+    Ok(now)
+}
+
+// Must match DAILY_NOTES_FOLDER_ID in @clutter/domain
+const DAILY_NOTES_FOLDER_ID: &str = "__daily_notes__";
+
+/// All non-deleted daily notes, newest date first
+#[tauri::command]
+pub fn get_all_daily_notes(state: State<DbConnection>) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM notes
+             WHERE daily_note_date IS NOT NULL AND deleted_at IS NULL
+             ORDER BY daily_note_date DESC, updated_at DESC",
+            NOTE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map([], note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+/// Return the daily note for `date` (YYYY-MM-DD), creating it when missing
+/// New notes get `template_content` (or empty content) and land in the daily notes folder
+#[tauri::command]
+pub fn ensure_daily_note(
+    date: String,
+    template_content: Option<String>,
+    state: State<DbConnection>,
+) -> Result<DailyNoteResult, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    find_or_create_daily_note(conn, &date, || Ok(template_content))
+}
+
+/// "Open today": like ensure_daily_note, but new notes use the `daily.template` setting
+/// with `{{date}}` replaced by the date, so the template lives in one place
+#[tauri::command]
+pub fn open_daily_note(date: String, state: State<DbConnection>) -> Result<DailyNoteResult, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    find_or_create_daily_note(conn, &date, || {
+        Ok(get_setting(conn, "daily.template")?.map(|template| template.replace("{{date}}", &date)))
+    })
+}
+
+/// Shared by ensure_daily_note / open_daily_note; `template` is only evaluated when creating
+fn find_or_create_daily_note(
+    conn: &Connection,
+    date: &str,
+    template: impl FnOnce() -> Result<Option<String>, String>,
+) -> Result<DailyNoteResult, String> {
+    let day = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid daily note date '{}': expected YYYY-MM-DD", date))?;
+    
+    // Uses idx_notes_daily_date; latest wins if duplicates slipped in (same as the frontend)
+    let existing = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM notes
+                 WHERE daily_note_date = ?1 AND deleted_at IS NULL
+                 ORDER BY updated_at DESC LIMIT 1",
+                NOTE_COLUMNS
+            ),
+            [date],
+            note_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    
+    if let Some(note) = existing {
+        let mut notes = [note];
+        populate_note_tags(conn, &mut notes)?;
+        let [note] = notes;
+        return Ok(DailyNoteResult { note, created: false });
+    }
+    
+    ensure_writable(conn)?;
+    
+    let now = now_timestamp();
+    let note = Note {
+        id: generate_id("note"),
+        // Fixed title; the frontend adds its "Today"/"Yesterday" prefix on display
+        title: day.format("%-d %b %Y").to_string(),
+        description: String::new(),
+        description_visible: true,
+        emoji: None,
+        content: template()?.unwrap_or_default(),
+        tags: Vec::new(),
+        tags_visible: true,
+        is_favorite: false,
+        folder_id: Some(DAILY_NOTES_FOLDER_ID.to_string()),
+        daily_note_date: Some(date.to_string()),
+        created_at: now.clone(),
+        updated_at: now,
+        deleted_at: None,
+        sort_order: None,
+        is_pinned: false,
+        is_locked: false,
+        copied_from: None,
+    };
+    write_note(conn, &note, false)?;
+    
+    println!("📅 Created daily note for {}", date);
+    
+    Ok(DailyNoteResult { note, created: true })
+}
+
+const TEMPLATE_COLUMNS: &str = "id, name, content, emoji, default_tags, created_at";
+
+fn template_from_row(row: &rusqlite::Row) -> Result<Template> {
+    let default_tags: String = row.get(4)?;
+    Ok(Template {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        content: row.get(2)?,
+        emoji: row.get(3)?,
+        // A hand-edited or corrupt column shouldn't hide the template
+        default_tags: serde_json::from_str(&default_tags).unwrap_or_default(),
+        created_at: row.get(5)?,
+    })
+}
+
+/// Save or update a note template (created_at is only honored on insert)
+#[tauri::command]
+pub fn save_template(template: Template, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let name = template.name.trim();
+    if name.is_empty() {
+        return Err(DbError::Validation("Template name cannot be empty".to_string()).into());
+    }
+    let allowed = max_note_bytes(conn)?;
+    if template.content.len() > allowed {
+        return Err(DbError::TooLarge { actual: template.content.len(), allowed }.into());
+    }
+    let emoji = normalize_emoji(template.emoji.as_deref())?;
+    let created_at = normalize_timestamp("createdAt", &template.created_at)?;
+    let default_tags = serde_json::to_string(&normalize_tags(conn, &template.default_tags)?)
+        .map_err(|e| e.to_string())?;
+    
+    conn.execute(
+        &format!(
+            "INSERT INTO templates ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                content = excluded.content,
+                emoji = excluded.emoji,
+                default_tags = excluded.default_tags",
+            TEMPLATE_COLUMNS
+        ),
+        (&template.id, name, &template.content, &emoji, &default_tags, &created_at),
+    )
+    .map_err(|e| e.to_string())?;
+    
+    println!("📋 Saved template: {}", name);
+    Ok(format!("Template saved: {}", template.id))
+}
+
+/// All templates, alphabetically
+#[tauri::command]
+pub fn load_all_templates(state: State<DbConnection>) -> Result<Vec<Template>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM templates ORDER BY name COLLATE NOCASE, created_at",
+            TEMPLATE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let templates = stmt
+        .query_map([], template_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Template>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(templates)
+}
+
+/// Delete a template; notes created from it are unaffected
+#[tauri::command]
+pub fn delete_template(template_id: String, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let deleted = conn
+        .execute("DELETE FROM templates WHERE id = ?1", [&template_id])
+        .map_err(|e| e.to_string())?;
+    if deleted == 0 {
+        return Err(format!("Template '{}' not found", template_id));
+    }
+    
+    println!("🗑️ Deleted template: {}", template_id);
+    Ok(format!("Template deleted: {}", template_id))
+}
+
+/// Create an untitled root note `new_id` pre-filled with a template's content, emoji and default tags
+#[tauri::command]
+pub fn create_note_from_template(
+    template_id: String,
+    new_id: String,
+    state: State<DbConnection>,
+) -> Result<Note, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let template = tx
+        .query_row(
+            &format!("SELECT {} FROM templates WHERE id = ?1", TEMPLATE_COLUMNS),
+            [&template_id],
+            template_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Template '{}' not found", template_id))?;
+    
+    // write_note upserts, so an existing id would silently be overwritten
+    let id_taken = tx
+        .query_row("SELECT 1 FROM notes WHERE id = ?1", [&new_id], |_| Ok(()))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if id_taken {
+        return Err(format!("Note '{}' already exists", new_id));
+    }
+    
+    let now = now_timestamp();
+    let note = Note {
+        id: new_id,
+        title: String::new(),
+        description: String::new(),
+        description_visible: true,
+        emoji: template.emoji,
+        content: template.content,
+        tags: normalize_tags(&tx, &template.default_tags)?,
+        tags_visible: true,
+        is_favorite: false,
+        folder_id: None,
+        daily_note_date: None,
+        created_at: now.clone(),
+        updated_at: now,
+        deleted_at: None,
+        sort_order: None,
+        is_pinned: false,
+        is_locked: false,
+        copied_from: None,
+    };
+    write_note(&tx, &note, false)?;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("📋 Created note {} from template {}", note.id, template_id);
+    Ok(note)
+}
+
+// 🔒 Per-note locks: AES-256-GCM with a PBKDF2-HMAC-SHA256 key from the note's own passphrase
+const NOTE_KEY_ITERATIONS: u32 = 600_000;
+const NOTE_SALT_LEN: usize = 16;
+// AES-GCM's 96-bit nonce
+const NOTE_NONCE_LEN: usize = 12;
+const LOCKED_NOTE_PLACEHOLDER: &str = r#"{"type":"doc","content":[{"type":"paragraph","attrs":{"blockId":null},"content":[{"type":"text","text":"🔒 This note is locked"}]}]}"#;
+
+fn derive_note_key(passphrase: &str, salt: &[u8]) -> Key<Aes256Gcm> {
+    let mut key = Key::<Aes256Gcm>::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, NOTE_KEY_ITERATIONS, &mut key);
+    key
+}
+
+/// Encrypt a note's content with `passphrase` and replace it with a placeholder
+/// The plaintext leaves the FTS index via the update trigger; regular saves can't overwrite a locked note's content
+#[tauri::command]
+pub fn lock_note(note_id: String, passphrase: String, state: State<DbConnection>) -> Result<String, String> {
+    if passphrase.is_empty() {
+        return Err(DbError::Validation("Passphrase cannot be empty".to_string()).into());
+    }
+    
+    // Key derivation is deliberately slow - do it before taking the connection lock
+    let mut salt = [0u8; NOTE_SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let cipher = Aes256Gcm::new(&derive_note_key(&passphrase, &salt));
+    
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let (content, is_locked): (String, bool) = conn
+        .query_row("SELECT content, is_locked FROM notes WHERE id = ?1", [&note_id], |row| {
+            Ok((row.get(0)?, row.get::<_, i32>(1)? != 0))
+        })
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Note '{}' not found", note_id))?;
+    if is_locked {
+        return Err(format!("Note '{}' is already locked", note_id));
+    }
+    
+    let encrypted = cipher
+        .encrypt(&nonce, content.as_bytes())
+        .map_err(|e| format!("Failed to encrypt note: {}", e))?;
+    
+    // secure_delete zeroes the freed plaintext pages instead of leaving them in the file
+    // It's switched back off whether or not the write succeeds, so it never outlives this call
+    conn.pragma_update(None, "secure_delete", true).map_err(|e| e.to_string())?;
+    let written = (|| -> Result<(), String> {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE notes SET content = ?1, content_hash = '', locked_content = ?2, lock_salt = ?3,
+                    lock_nonce = ?4, is_locked = 1, updated_at = ?5
+             WHERE id = ?6",
+            (
+                LOCKED_NOTE_PLACEHOLDER,
+                &encrypted,
+                &salt[..],
+                nonce.as_slice(),
+                now_timestamp(),
+                &note_id,
+            ),
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    })();
+    let restored = conn.pragma_update(None, "secure_delete", false).map_err(|e| e.to_string());
+    written?;
+    restored?;
+    
+    println!("🔒 Locked note: {}", note_id);
+    Ok(format!("Note '{}' locked", note_id))
+}
+
+/// Ciphertext, salt and nonce of a locked note
+struct LockedNote {
+    encrypted: Vec<u8>,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+}
+
+/// Sizes are checked here: a truncated row must be an error, not a panic in Nonce::from_slice
+fn load_locked_note(conn: &Connection, note_id: &str) -> Result<LockedNote, String> {
+    let (encrypted, salt, nonce): (Vec<u8>, Vec<u8>, Vec<u8>) = conn
+        .query_row(
+            "SELECT locked_content, lock_salt, lock_nonce FROM notes WHERE id = ?1 AND is_locked = 1",
+            [note_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Locked note '{}' not found", note_id))?;
+    if salt.len() != NOTE_SALT_LEN || nonce.len() != NOTE_NONCE_LEN {
+        return Err(DbError::Validation(format!(
+            "Locked note '{}' is corrupted (salt {} / nonce {} bytes)",
+            note_id,
+            salt.len(),
+            nonce.len()
+        ))
+        .into());
+    }
+    Ok(LockedNote { encrypted, salt, nonce })
+}
+
+fn decrypt_note(passphrase: &str, locked: &LockedNote) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(&derive_note_key(passphrase, &locked.salt));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&locked.nonce), locked.encrypted.as_slice())
+        .map_err(|_| DbError::Validation("Wrong passphrase for this note".to_string()))?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+/// Decrypt a locked note and return its content; the note stays locked on disk
+/// (use remove_note_lock to drop the lock for good)
+#[tauri::command]
+pub fn unlock_note(note_id: String, passphrase: String, state: State<DbConnection>) -> Result<String, String> {
+    let locked = {
+        let conn_guard = state.0.lock().unwrap();
+        let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+        load_locked_note(conn, &note_id)?
+    };
+    
+    // Key derivation runs without holding the connection lock
+    let content = decrypt_note(&passphrase, &locked)?;
+    
+    println!("🔓 Unlocked note for viewing: {}", note_id);
+    Ok(content)
+}
+
+/// Permanently remove a note's lock: decrypt it, write the plaintext back (FTS picks it up
+/// again via the update trigger) and clear the lock columns. Returns the content
+#[tauri::command]
+pub fn remove_note_lock(note_id: String, passphrase: String, state: State<DbConnection>) -> Result<String, String> {
+    let locked = {
+        let conn_guard = state.0.lock().unwrap();
+        let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+        ensure_writable(conn)?;
+        load_locked_note(conn, &note_id)?
+    };
+    
+    let content = decrypt_note(&passphrase, &locked)?;
+    
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    // Matching the nonce makes sure we clear the same lock we just decrypted
+    let updated = conn
+        .execute(
+            "UPDATE notes SET content = ?1, content_hash = ?2, locked_content = NULL, lock_salt = NULL,
+                    lock_nonce = NULL, is_locked = 0, updated_at = ?3
+             WHERE id = ?4 AND is_locked = 1 AND lock_nonce = ?5",
+            (&content, content_hash(&content), now_timestamp(), &note_id, &locked.nonce),
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Note '{}' changed while it was being unlocked; try again", note_id));
+    }
+    
+    println!("🔓 Removed lock from note: {}", note_id);
+    Ok(content)
+}
+
+/// Pin or unpin a note (independent of favorites)
+/// Targeted update so regular saves never clobber the pinned state
+#[tauri::command]
+pub fn set_note_pinned(note_id: String, pinned: bool, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let updated = conn
+        .execute(
+            "UPDATE notes SET is_pinned = ?1 WHERE id = ?2",
+            (pinned as i32, &note_id),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    if updated == 0 {
+        return Err(format!("Note '{}' not found", note_id));
+    }
+    
+    Ok(format!("Note {}: {}", if pinned { "pinned" } else { "unpinned" }, note_id))
+}
+
+/// Load metadata for all notes without the heavy `content` column
+/// The editor calls load_note only for the note actually opened
+#[tauri::command]
+pub fn load_notes_metadata(state: State<DbConnection>) -> Result<Vec<NoteMeta>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // Same filtering/order as load_all_notes' defaults (pinned first, then newest edit), minus content
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, description, description_visible, emoji, tags_visible, 
+             is_favorite, folder_id, daily_note_date, created_at, updated_at, deleted_at, is_locked,
+             is_pinned, sort_order
+             FROM notes 
+             ORDER BY is_pinned DESC, updated_at DESC"
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<NoteMeta> = stmt
+        .query_map([], |row| {
+            Ok(NoteMeta {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                description: row.get(2)?,
+                description_visible: row.get::<_, i32>(3)? != 0,
+                emoji: row.get(4)?,
+                tags: Vec::new(), // Will be populated below
+                tags_visible: row.get::<_, i32>(5)? != 0,
+                is_favorite: row.get::<_, i32>(6)? != 0,
+                folder_id: row.get(7)?,
+                daily_note_date: row.get(8)?,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
+                deleted_at: row.get(11)?,
+                is_locked: row.get::<_, i32>(12)? != 0,
+                is_pinned: row.get::<_, i32>(13)? != 0,
+                sort_order: row.get(14)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<NoteMeta>>>()
+        .map_err(|e| e.to_string())?;
+    
+    let mut tags_by_note = load_tags_by_note(conn, None)?;
+    for note in &mut notes {
+        note.tags = tags_by_note.remove(&note.id).unwrap_or_default();
+    }
+    
+    Ok(notes)
+}
+
+// TipTap JSON is mostly markup, so read well past max_chars to find that much text
+const PREVIEW_WINDOW_FACTOR: i64 = 8;
+const PREVIEW_WINDOW_SLACK: i64 = 512;
+
+/// Plain text from the start of a (possibly truncated) TipTap JSON document
+/// Reads `"text":"..."` values in order; a `"content":[` or hardBreak between two of them
+/// starts a new block, which becomes a space. Content that isn't a document is used as-is
+fn preview_text(prefix: &str, max_chars: usize) -> String {
+    let mut raw = String::new();
+    if prefix.trim_start().starts_with('{') {
+        let mut rest = prefix;
+        while let Some(at) = rest.find("\"text\":\"") {
+            let gap = &rest[..at];
+            if gap.contains("\"content\":[") || gap.contains("\"hardBreak\"") {
+                raw.push(' ');
+            }
+            let value = &rest[at + 8..];
+            // Find the closing quote, skipping escaped characters
+            let mut end = None;
+            let mut escaped = false;
+            for (i, c) in value.char_indices() {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => {
+                        end = Some(i);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            // A value cut off by the window: keep what's there, minus any half-read escape
+            let literal = match end {
+                Some(i) => &value[..i],
+                None => match value.rfind('\\') {
+                    Some(i) if value.len() - i <= 6 => &value[..i],
+                    _ => value,
+                },
+            };
+            if let Ok(text) = serde_json::from_str::<String>(&format!("\"{}\"", literal)) {
+                raw.push_str(&text);
+            }
+            match end {
+                Some(i) => rest = &value[i + 1..],
+                None => break,
+            }
+            if raw.chars().count() > max_chars * 2 {
+                break;
+            }
+        }
+    } else {
+        raw.push_str(prefix);
+    }
+    
+    let collapsed = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed.chars().take(max_chars).collect()
+}
+
+/// id/title/emoji plus the first `max_chars` characters of plain text for every note,
+/// in load_notes_metadata order - list previews without shipping whole documents over IPC
+#[tauri::command]
+pub fn load_note_previews(max_chars: i64, state: State<DbConnection>) -> Result<Vec<NotePreview>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    if max_chars <= 0 {
+        return Err(DbError::Validation(format!("Invalid max_chars {}: must be positive", max_chars)).into());
+    }
+    let window = max_chars
+        .saturating_mul(PREVIEW_WINDOW_FACTOR)
+        .saturating_add(PREVIEW_WINDOW_SLACK);
+    
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, title, emoji, substr(content, 1, ?1)
+             FROM notes
+             ORDER BY updated_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let previews = stmt
+        .query_map([window], |row| {
+            let prefix: String = row.get(3)?;
+            Ok(NotePreview {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                emoji: row.get(2)?,
+                preview: preview_text(&prefix, max_chars as usize),
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<NotePreview>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(previews)
+}
+
+// Default bm25 column weights: a title hit counts ten times a body hit
+const SEARCH_TITLE_WEIGHT: f64 = 10.0;
+const SEARCH_CONTENT_WEIGHT: f64 = 1.0;
+// Result order over a search_match_query: FTS hits by bm25 (lower is better), then tag-only hits
+const SEARCH_RESULT_ORDER: &str = "match_group, match_rank, updated_at DESC";
+
+/// Validated bm25 weight for a search column
+fn search_weight(name: &str, weight: Option<f64>, default: f64) -> Result<f64, DbError> {
+    match weight {
+        None => Ok(default),
+        Some(w) if w.is_finite() && w >= 0.0 => Ok(w),
+        Some(w) => Err(DbError::Validation(format!("Invalid {} {}: must be a non-negative number", name, w))),
+    }
+}
+
+/// The match set behind search_notes: FTS5 ranked hits unioned with tag matches not
+/// already found via FTS. Returns the SQL (selecting notes.*, match_group, match_rank) and its params
+/// `substring` searches the trigram index instead (errors if it isn't enabled)
+/// `weights` are the bm25 (title, content) column weights used for match_rank
+fn search_match_query(
+    conn: &Connection,
+    query: &str,
+    substring: bool,
+    weights: (f64, f64),
+) -> Result<(String, Vec<String>), String> {
+    let fts_table = if substring {
+        if !substring_index_enabled(conn)? {
+            return Err(DbError::Validation(
+                "Substring search is not enabled (init_database option substring_index)".to_string(),
+            )
+            .into());
+        }
+        "notes_fts_trigram"
+    } else {
+        "notes_fts"
+    };
+    
+    let terms = search_terms(query);
+    let mut params: Vec<String> = vec![query.to_string()];
+    params.extend(terms.iter().map(|t| format!("%{}%", escape_like(t))));
+    
+    let matches = format!(
+        "SELECT notes.*, 0 AS match_group, bm25({fts}, 0.0, {title}, {content}) AS match_rank
+         FROM notes
+         JOIN {fts} ON notes.id = {fts}.note_id
+         WHERE {fts} MATCH ?1 AND notes.deleted_at IS NULL
+         {tags}",
+        fts = fts_table,
+        title = weights.0,
+        content = weights.1,
+        tags = tag_search_branch(terms.len(), fts_table)
+    );
+    
+    Ok((matches, params))
+}
+
+/// COUNT(*) over a search_match_query result
+fn count_search_matches(conn: &Connection, matches: &str, params: &[String]) -> Result<i64, String> {
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM ({})", matches),
+        rusqlite::params_from_iter(params.iter()),
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Number of notes search_notes would match, without fetching any rows (live "42 matches" counter)
+#[tauri::command]
+pub fn count_search_results(
+    query: String,
+    substring: Option<bool>,
+    state: State<DbConnection>,
+) -> Result<i64, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let weights = (SEARCH_TITLE_WEIGHT, SEARCH_CONTENT_WEIGHT);
+    let (matches, params) = search_match_query(conn, &query, substring.unwrap_or(false), weights)?;
+    count_search_matches(conn, &matches, &params)
+}
+
+/// Search notes using FTS5 (full-text search)
+/// Returns ranked results matching the query: title/content matches first (by rank),
+/// then notes that only match through a tag's name or description
+/// Paged with `limit` (default 50) / `offset` (default 0); `total` counts every match
+/// `substring: true` matches inside words ("grate" finds "integrated") via the opt-in trigram index
+/// `title_weight` / `content_weight` tune bm25 ranking (defaults 10.0 / 1.0: title hits first)
+#[tauri::command]
+pub fn search_notes(
+    query: String,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    substring: Option<bool>,
+    title_weight: Option<f64>,
+    content_weight: Option<f64>,
+    state: State<DbConnection>,
+) -> Result<SearchResults, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let limit = limit.unwrap_or(50);
+    let offset = offset.unwrap_or(0);
+    if limit < 0 || offset < 0 {
+        return Err(format!("Invalid paging: limit {} / offset {} must not be negative", limit, offset));
+    }
+    
+    let weights = (
+        search_weight("title_weight", title_weight, SEARCH_TITLE_WEIGHT)?,
+        search_weight("content_weight", content_weight, SEARCH_CONTENT_WEIGHT)?,
+    );
+    let (matches, params) = search_match_query(conn, &query, substring.unwrap_or(false), weights)?;
+    let total = count_search_matches(conn, &matches, &params)?;
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {cols} FROM ({matches}) AS notes
+             ORDER BY {order}
+             LIMIT {limit} OFFSET {offset}",
+            cols = NOTE_COLUMNS,
+            matches = matches,
+            order = SEARCH_RESULT_ORDER,
+            limit = limit,
+            offset = offset,
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    // Load tags for search results (batch load)
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(SearchResults { notes, total })
+}
+
+/// An FTS5 string literal: every input is matched as plain text, never parsed as syntax
+fn fts_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// FTS5 MATCH expression for search_notes_structured, or None when there's nothing to search for
+/// `mode` "all" ANDs the terms, "any" ORs them; `phrase` is always required as an exact phrase
+fn structured_match_query(terms: &[String], mode: &str, phrase: Option<&str>) -> Result<Option<String>, DbError> {
+    let operator = match mode {
+        "all" => " AND ",
+        "any" => " OR ",
+        other => return Err(DbError::Validation(format!("Invalid mode '{}': expected all or any", other))),
+    };
+    
+    let quoted: Vec<String> = terms
+        .iter()
+        .map(|term| term.trim())
+        .filter(|term| !term.is_empty())
+        .map(fts_quote)
+        .collect();
+    let phrase = phrase.map(str::trim).filter(|p| !p.is_empty()).map(fts_quote);
+    
+    let expression = match (quoted.is_empty(), phrase) {
+        (true, None) => None,
+        (true, Some(phrase)) => Some(phrase),
+        (false, None) => Some(quoted.join(operator)),
+        (false, Some(phrase)) => Some(format!("({}) AND {}", quoted.join(operator), phrase)),
+    };
+    Ok(expression)
+}
+
+/// search_notes for an advanced-search form: the MATCH string is built here from plain
+/// terms (`mode` "all" / "any") and an optional exact `phrase`, so users never write FTS5 syntax
+/// Ranked like search_notes (tag matches still require every word)
+#[tauri::command]
+pub fn search_notes_structured(
+    terms: Vec<String>,
+    mode: String,
+    phrase: Option<String>,
+    state: State<DbConnection>,
+) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let query = match structured_match_query(&terms, &mode, phrase.as_deref())? {
+        Some(query) => query,
+        None => return Ok(Vec::new()),
+    };
+    
+    let weights = (SEARCH_TITLE_WEIGHT, SEARCH_CONTENT_WEIGHT);
+    let (matches, params) = search_match_query(conn, &query, false, weights)?;
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM ({}) AS notes ORDER BY {}",
+            NOTE_COLUMNS, matches, SEARCH_RESULT_ORDER
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+/// Upsert a folder and its tag relationships (shared by save_folder and imports)
+fn write_folder(conn: &Connection, folder: &Folder) -> Result<(), String> {
+    let emoji = normalize_emoji(folder.emoji.as_deref())?;
+    let created_at = normalize_timestamp("createdAt", &folder.created_at)?;
+    let updated_at = normalize_timestamp("updatedAt", &folder.updated_at)?;
+    let deleted_at = normalize_optional_timestamp("deletedAt", folder.deleted_at.as_deref())?;
+    
+    // Active folders can't be placed under a trashed parent. Only checked when parent_id changes,
+    // so renaming a child left under a (non-cascade) trashed folder still works. A missing parent
+    // is always rejected (foreign keys may be off); the message keeps SQLite's FK wording, which
+    // the frontend recovers from
+    if let Some(parent_id) = &folder.parent_id {
+        let stored_parent: Option<Option<String>> = conn
+            .prepare_cached("SELECT parent_id FROM folders WHERE id = ?1")
+            .map_err(|e| e.to_string())?
+            .query_row([&folder.id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let reparenting_active =
+            deleted_at.is_none() && stored_parent.flatten().as_deref() != Some(parent_id.as_str());
+        let parent_exists = if reparenting_active {
+            ensure_parent_not_trashed(conn, parent_id)?
+        } else {
+            conn.prepare_cached("SELECT 1 FROM folders WHERE id = ?1")
+                .map_err(|e| e.to_string())?
+                .exists([parent_id])
+                .map_err(|e| e.to_string())?
+        };
+        if !parent_exists {
+            return Err(DbError::Validation(format!(
+                "Parent folder '{}' not found (FOREIGN KEY constraint failed)",
+                parent_id
+            ))
+            .into());
+        }
+    }
+    
+    // Upsert folder
+    let mut upsert = conn
+        .prepare_cached(
+            "INSERT INTO folders 
+            (id, name, parent_id, description, description_visible, color, emoji, 
+             tags_visible, is_favorite, is_expanded, created_at, updated_at, deleted_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                parent_id = excluded.parent_id,
+                description = excluded.description,
+                description_visible = excluded.description_visible,
+                color = excluded.color,
+                emoji = excluded.emoji,
+                tags_visible = excluded.tags_visible,
+                is_favorite = excluded.is_favorite,
+                is_expanded = excluded.is_expanded,
+                updated_at = excluded.updated_at,
+                deleted_at = excluded.deleted_at",
+        )
+        .map_err(|e| e.to_string())?;
+    upsert
+        .execute((
+            &folder.id,
+            &folder.name,
+            &folder.parent_id,
+            &folder.description,
+            folder.description_visible as i32,
+            &folder.color,
+            &emoji,
+            folder.tags_visible as i32,
+            folder.is_favorite as i32,
+            folder.is_expanded as i32,
+            &created_at,
+            &updated_at,
+            &deleted_at,
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let tags = normalize_tags(conn, &folder.tags)?;
+    
+    // Ensure all tags exist (prevent FK violations)
+    let mut ensure_tag = conn
+        .prepare_cached(
+            "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at)
+             VALUES (?1, '', 1, 0, NULL, ?2, ?2)
+             ON CONFLICT(name) DO NOTHING",
+        )
+        .map_err(|e| e.to_string())?;
+    for tag in &tags {
+        ensure_tag.execute((tag, &updated_at)).map_err(|e| e.to_string())?;
+    }
+    
+    // Delete existing tag relationships
+    conn.prepare_cached("DELETE FROM folder_tags WHERE folder_id = ?1")
+        .map_err(|e| e.to_string())?
+        .execute([&folder.id])
+        .map_err(|e| e.to_string())?;
+    
+    // Insert new tag relationships
+    let mut insert_tag = conn
+        .prepare_cached("INSERT INTO folder_tags (folder_id, tag_name) VALUES (?1, ?2)")
+        .map_err(|e| e.to_string())?;
+    for tag in &tags {
+        insert_tag.execute((&folder.id, tag)).map_err(|e| e.to_string())?;
+    }
+    
+    Ok(())
+}
+
+/// Save or update a folder
+#[tauri::command]
+pub fn save_folder(folder: Folder, state: State<DbConnection>, history: State<DeleteHistory>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    println!(
+        "💾 Saving folder {} | name: {}",
+        truncate_chars(&folder.id, 20),
+        folder.name
+    );
+    
+    let was_active = folder.deleted_at.is_some() && trash_state(conn, "folders", &folder.id)? == Some(None);
+    write_folder(conn, &folder)?;
+    if was_active {
+        if let Some(Some(deleted_at)) = trash_state(conn, "folders", &folder.id)? {
+            history.push("folder", &folder.id, deleted_at);
+        }
+    }
+    
+    Ok(format!("Folder saved: {}", folder.id))
+}
+
+/// Expand or collapse a folder in the tree
+/// UI-only state: leaves updated_at alone so browsing never marks folders as modified
+#[tauri::command]
+pub fn set_folder_expanded(folder_id: String, expanded: bool, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let updated = conn
+        .prepare_cached("UPDATE folders SET is_expanded = ?1 WHERE id = ?2")
+        .map_err(|e| e.to_string())?
+        .execute((expanded as i32, &folder_id))
+        .map_err(|e| e.to_string())?;
+    
+    if updated == 0 {
+        return Err(format!("Folder '{}' not found", folder_id));
+    }
+    
+    Ok(format!("Folder {}: {}", if expanded { "expanded" } else { "collapsed" }, folder_id))
+}
+
+/// Load all folders
+#[tauri::command]
+pub fn load_all_folders(state: State<DbConnection>) -> Result<Vec<Folder>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // Load all folders (including deleted ones - filtering happens in frontend)
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM folders", FOLDER_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    
+    let mut folders: Vec<Folder> = stmt
+        .query_map([], folder_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Folder>>>()
+        .map_err(|e| e.to_string())?;
+    
+    // Load all tags in one query (Apple Notes approach - no N+1)
+    let mut tag_stmt = conn
+        .prepare("SELECT folder_id, tag_name FROM folder_tags")
+        .map_err(|e| e.to_string())?;
+    
+    let tag_rows = tag_stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    
+    let mut tags_by_folder: HashMap<String, Vec<String>> = HashMap::new();
+    for result in tag_rows {
+        let (folder_id, tag) = result.map_err(|e| e.to_string())?;
+        tags_by_folder.entry(folder_id).or_insert_with(Vec::new).push(tag);
+    }
+    
+    for folder in &mut folders {
+        folder.tags = tags_by_folder.remove(&folder.id).unwrap_or_default();
+    }
+    
+    Ok(folders)
+}
+
+/// Direct, non-deleted children of `parent_id` (None = root folders), ordered by name
+/// `has_children` lets the tree draw expand arrows without loading the next level
+#[tauri::command]
+pub fn get_child_folders(parent_id: Option<String>, state: State<DbConnection>) -> Result<Vec<FolderNode>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // `IS` matches NULL too, so the same statement serves the root level
+    let mut stmt = conn
+        .prepare_cached(&format!(
+            "SELECT {}, EXISTS (
+                 SELECT 1 FROM folders child
+                 WHERE child.parent_id = folders.id AND child.deleted_at IS NULL
+             )
+             FROM folders
+             WHERE parent_id IS ?1 AND deleted_at IS NULL
+             ORDER BY name COLLATE NOCASE",
+            FOLDER_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let rows = stmt
+        .query_map([&parent_id], |row| Ok((folder_from_row(row)?, row.get::<_, bool>(13)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(Folder, bool)>>>()
+        .map_err(|e| e.to_string())?;
+    
+    let (mut folders, has_children): (Vec<Folder>, Vec<bool>) = rows.into_iter().unzip();
+    populate_folder_tags(conn, &mut folders)?;
+    
+    Ok(folders
+        .into_iter()
+        .zip(has_children)
+        .map(|(folder, has_children)| FolderNode { folder, has_children })
+        .collect())
+}
+
+/// Resolve the ancestry of a folder for breadcrumbs
+/// Returns folders ordered root-first, ending with the requested folder
+#[tauri::command]
+pub fn get_folder_path(folder_id: String, state: State<DbConnection>) -> Result<Vec<Folder>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM folders WHERE id = ?1", FOLDER_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    
+    let mut path: Vec<Folder> = Vec::new();
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut current_id = Some(folder_id.clone());
+    
+    // Walk parent_id upward until we reach a root folder
+    while let Some(id) = current_id {
+        // Cycle / depth guard: a corrupted hierarchy must never hang the call
+        if !visited.insert(id.clone()) {
+            return Err(format!("Folder hierarchy contains a cycle at '{}'", id));
+        }
+        if path.len() >= MAX_FOLDER_DEPTH {
+            return Err(format!("Folder hierarchy exceeds max depth of {}", MAX_FOLDER_DEPTH));
+        }
+        
+        let folder = stmt
+            .query_row([&id], folder_from_row)
+            .optional()
+            .map_err(|e| e.to_string())?;
+        
+        match folder {
+            Some(folder) => {
+                current_id = folder.parent_id.clone();
+                path.push(folder);
+            }
+            // Starting folder missing is an error; a missing ancestor just ends the chain
+            None if path.is_empty() => return Err(format!("Folder '{}' not found", folder_id)),
+            None => break,
+        }
+    }
+    
+    path.reverse();
+    populate_folder_tags(conn, &mut path)?;
+    
+    Ok(path)
+}
+
+/// The folder a note lives in (with tags), or None when the note is at the root
+/// A dangling folder_id also yields None; an unknown note is an error
+#[tauri::command]
+pub fn get_note_folder(note_id: String, state: State<DbConnection>) -> Result<Option<Folder>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let folder_id: Option<String> = conn
+        .prepare_cached("SELECT folder_id FROM notes WHERE id = ?1")
+        .map_err(|e| e.to_string())?
+        .query_row([&note_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Note '{}' not found", note_id))?;
+    let folder_id = match folder_id {
+        Some(folder_id) => folder_id,
+        None => return Ok(None),
+    };
+    
+    let folder = conn
+        .query_row(
+            &format!("SELECT {} FROM folders WHERE id = ?1", FOLDER_COLUMNS),
+            [&folder_id],
+            folder_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    
+    match folder {
+        Some(folder) => {
+            let mut folders = [folder];
+            populate_folder_tags(conn, &mut folders)?;
+            let [folder] = folders;
+            Ok(Some(folder))
+        }
+        None => Ok(None),
+    }
+}
+
+/// IDs of every non-deleted note in a folder and all of its descendants
+/// Primitive for subtree export / bulk actions; same depth-capped CTE as delete_folder's cascade
+#[tauri::command]
+pub fn get_folder_note_ids_recursive(folder_id: String, state: State<DbConnection>) -> Result<Vec<String>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} SELECT notes.id FROM notes
+             WHERE notes.folder_id IN (SELECT id FROM subtree) AND notes.deleted_at IS NULL
+             ORDER BY notes.created_at",
+            folder_subtree_cte()
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let note_ids = stmt
+        .query_map([&folder_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(note_ids)
+}
+
+/// Non-deleted note count and content bytes for a folder and all of its descendants
+#[tauri::command]
+pub fn get_folder_size(folder_id: String, state: State<DbConnection>) -> Result<FolderSize, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let exists = conn
+        .query_row("SELECT 1 FROM folders WHERE id = ?1", [&folder_id], |_| Ok(()))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if !exists {
+        return Err(format!("Folder '{}' not found", folder_id));
+    }
+    
+    // CAST AS BLOB so LENGTH counts bytes rather than characters
+    let (note_count, total_content_bytes) = conn
+        .query_row(
+            &format!(
+                "{} SELECT COUNT(*),
+                        COALESCE(SUM(LENGTH(CAST(content AS BLOB)) + COALESCE(LENGTH(locked_content), 0)), 0)
+                 FROM notes
+                 WHERE folder_id IN (SELECT id FROM subtree) AND deleted_at IS NULL",
+                folder_subtree_cte()
+            ),
+            [&folder_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    Ok(FolderSize { note_count, total_content_bytes, attachment_bytes: 0 })
+}
+
+/// One soft-delete recorded in DeleteHistory; `entity_type` is "note" or "folder"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedEntity {
+    pub entity_type: String,
+    pub id: String,
+    pub deleted_at: String,
+}
+
+/// deleted_at of a note/folder row, or None when the row doesn't exist
+fn trash_state(conn: &Connection, table: &str, id: &str) -> Result<Option<Option<String>>, String> {
+    conn.prepare_cached(&format!("SELECT deleted_at FROM {} WHERE id = ?1", table))
+        .map_err(|e| e.to_string())?
+        .query_row([id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// Soft-delete a folder (move to trash)
+/// With `cascade`, descendant folders and every note in the subtree are trashed too,
+/// and recorded so restore_folder can undo exactly this delete
+#[tauri::command]
+pub fn delete_folder(
+    folder_id: String,
+    cascade: bool,
+    state: State<DbConnection>,
+    history: State<DeleteHistory>,
+) -> Result<CascadeSummary, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let now = now_timestamp();
+    
+    let updated = tx
+        .execute(
+            "UPDATE folders SET deleted_at = ?1, updated_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            (&now, &folder_id),
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Folder '{}' not found or already deleted", folder_id));
+    }
+    
+    // A fresh delete replaces any stale cascade record for this folder
+    tx.execute("DELETE FROM folder_delete_cascade WHERE root_folder_id = ?1", [&folder_id])
+        .map_err(|e| e.to_string())?;
+    
+    let mut summary = CascadeSummary { folders: 1, notes: 0 };
+    
+    if cascade {
+        let subtree = folder_subtree_cte();
+        
+        // Record only items that are still active (already-trashed ones stay trashed on restore)
+        tx.execute(
+            &format!(
+                "{} INSERT OR IGNORE INTO folder_delete_cascade (root_folder_id, entity_type, entity_id)
+                 SELECT ?1, 'folder', folders.id FROM folders
+                 WHERE folders.id IN (SELECT id FROM subtree) AND folders.id != ?1 AND folders.deleted_at IS NULL",
+                subtree
+            ),
+            [&folder_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            &format!(
+                "{} INSERT OR IGNORE INTO folder_delete_cascade (root_folder_id, entity_type, entity_id)
+                 SELECT ?1, 'note', notes.id FROM notes
+                 WHERE notes.folder_id IN (SELECT id FROM subtree) AND notes.deleted_at IS NULL",
+                subtree
+            ),
+            [&folder_id],
+        )
+        .map_err(|e| e.to_string())?;
+        
+        summary.folders += tx
+            .execute(
+                "UPDATE folders SET deleted_at = ?2, updated_at = ?2 WHERE id IN (
+                    SELECT entity_id FROM folder_delete_cascade WHERE root_folder_id = ?1 AND entity_type = 'folder'
+                )",
+                (&folder_id, &now),
+            )
+            .map_err(|e| e.to_string())?;
+        summary.notes = tx
+            .execute(
+                "UPDATE notes SET deleted_at = ?2, updated_at = ?2 WHERE id IN (
+                    SELECT entity_id FROM folder_delete_cascade WHERE root_folder_id = ?1 AND entity_type = 'note'
+                )",
+                (&folder_id, &now),
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    history.push("folder", &folder_id, now);
+    
+    println!("🗑️ Soft-deleted folder: {} (cascade: {})", folder_id, cascade);
+    Ok(summary)
+}
+
+/// Restore a soft-deleted folder along with the items its cascade trashed
+#[tauri::command]
+pub fn restore_folder(folder_id: String, state: State<DbConnection>) -> Result<CascadeSummary, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    restore_folder_cascade(conn, &folder_id)
+}
+
+/// restore_folder's body, shared with undo_last_delete
+fn restore_folder_cascade(conn: &mut Connection, folder_id: &str) -> Result<CascadeSummary, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let now = now_timestamp();
+    
+    let restored = tx
+        .execute(
+            "UPDATE folders SET deleted_at = NULL, updated_at = ?1 WHERE id = ?2 AND deleted_at IS NOT NULL",
+            (&now, folder_id),
+        )
+        .map_err(|e| e.to_string())?;
+    if restored == 0 {
+        return Err(format!("Folder '{}' not found in trash", folder_id));
+    }
+    
+    let folders = tx
+        .execute(
+            "UPDATE folders SET deleted_at = NULL, updated_at = ?2 WHERE id IN (
+                SELECT entity_id FROM folder_delete_cascade WHERE root_folder_id = ?1 AND entity_type = 'folder'
+            )",
+            (folder_id, &now),
+        )
+        .map_err(|e| e.to_string())?;
+    let notes = tx
+        .execute(
+            "UPDATE notes SET deleted_at = NULL, updated_at = ?2 WHERE id IN (
+                SELECT entity_id FROM folder_delete_cascade WHERE root_folder_id = ?1 AND entity_type = 'note'
+            )",
+            (folder_id, &now),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    tx.execute("DELETE FROM folder_delete_cascade WHERE root_folder_id = ?1", [folder_id])
+        .map_err(|e| e.to_string())?;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    Ok(CascadeSummary { folders: folders + 1, notes })
+}
+
+/// Restore the most recent soft-delete of this session (note or folder, see DeleteHistory)
+/// Entries whose item was since restored, re-deleted or permanently deleted are skipped;
+/// a folder comes back with everything its cascade trashed. None when there's nothing to undo
+#[tauri::command]
+pub fn undo_last_delete(
+    state: State<DbConnection>,
+    history: State<DeleteHistory>,
+) -> Result<Option<DeletedEntity>, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    loop {
+        let entry = match history.0.lock().unwrap().pop_back() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let table = if entry.entity_type == "folder" { "folders" } else { "notes" };
+        // Only undo the exact delete we recorded, not a later one of the same item
+        if trash_state(conn, table, &entry.id)? != Some(Some(entry.deleted_at.clone())) {
+            continue;
+        }
+        
+        if entry.entity_type == "folder" {
+            restore_folder_cascade(conn, &entry.id)?;
+        } else {
+            conn.execute(
+                "UPDATE notes SET deleted_at = NULL, updated_at = ?1 WHERE id = ?2",
+                (now_timestamp(), &entry.id),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        
+        println!("↩️ Undid delete of {} {}", entry.entity_type, entry.id);
+        return Ok(Some(entry));
+    }
+}
+
+/// Save or update tag metadata
+#[tauri::command]
+pub fn save_tag(tag: Tag, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let name = normalize_tag_name(&tag.name, lowercase_tags(conn)?);
+    if name.is_empty() {
+        return Err("Tag name cannot be empty".to_string());
+    }
+    let created_at = normalize_timestamp("createdAt", &tag.created_at)?;
+    let updated_at = normalize_timestamp("updatedAt", &tag.updated_at)?;
+    let deleted_at = normalize_optional_timestamp("deletedAt", tag.deleted_at.as_deref())?;
+    
+    println!("💾 Saving tag metadata: {}", name);
+    
+    conn.execute(
+        "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at, deleted_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(name) DO UPDATE SET
+            description = excluded.description,
+            description_visible = excluded.description_visible,
+            is_favorite = excluded.is_favorite,
+            color = excluded.color,
+            updated_at = excluded.updated_at,
+            deleted_at = excluded.deleted_at",
+        (
+            &name,
+            &tag.description,
+            tag.description_visible as i32,
+            tag.is_favorite as i32,
+            &tag.color,
+            &created_at,
+            &updated_at,
+            &deleted_at,
+        ),
+    )
+    .map_err(|e| e.to_string())?;
+    
+    Ok(format!("Tag saved: {}", name))
+}
+
+/// Load all tag metadata
+#[tauri::command]
+pub fn load_all_tags(state: State<DbConnection>) -> Result<Vec<Tag>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM tags", TAG_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    
+    let tags: Vec<Tag> = stmt
+        .query_map([], tag_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Tag>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(tags)
+}
+
+/// One-shot fix for tags that differ only by case/whitespace ("Work", "work", " work ")
+/// Each group is merged into the oldest variant's canonical name; returns how many tags were merged away
+#[tauri::command]
+pub fn merge_duplicate_tags(state: State<DbConnection>) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let merged = merge_tag_variants(conn)?;
+    
+    println!("🏷️ Merged {} duplicate tags", merged);
+    
+    Ok(merged)
+}
+
+/// merge_duplicate_tags' body: one transaction over every tag group
+fn merge_tag_variants(conn: &mut Connection) -> Result<usize, String> {
+    let lowercase = lowercase_tags(conn)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    // Oldest first, so the earliest variant decides the canonical spelling
+    let names: Vec<String> = tx
+        .prepare("SELECT name FROM tags ORDER BY created_at, name")
+        .map_err(|e| e.to_string())?
+        .query_map([], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>>>()
+        .map_err(|e| e.to_string())?;
+    
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        let key = normalize_tag_name(&name, true);
+        if key.is_empty() {
+            continue;
+        }
+        match group_index.get(&key) {
+            Some(&i) => groups[i].1.push(name),
+            None => {
+                group_index.insert(key, groups.len());
+                groups.push((name.clone(), vec![name]));
+            }
+        }
+    }
+    
+    let now = now_timestamp();
+    let mut merged = 0;
+    let mut affected_notes: HashSet<String> = HashSet::new();
+    let mut affected_folders: HashSet<String> = HashSet::new();
+    
+    for (oldest, variants) in groups {
+        let canonical = normalize_tag_name(&oldest, lowercase);
+        if variants.len() == 1 && variants[0] == canonical {
+            continue;
+        }
+        
+        // Canonical spelling may not exist yet (e.g. only " work " and "Work") - seed it from the oldest
+        if !variants.contains(&canonical) {
+            tx.execute(
+                "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at, deleted_at)
+                 SELECT ?1, description, description_visible, is_favorite, color, created_at, ?3, deleted_at
+                 FROM tags WHERE name = ?2",
+                (&canonical, &oldest, &now),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        
+        for variant in variants.iter().filter(|v| **v != canonical) {
+            for (table, owner, affected) in [
+                ("note_tags", "note_id", &mut affected_notes),
+                ("folder_tags", "folder_id", &mut affected_folders),
+            ] {
+                let mut stmt = tx
+                    .prepare(&format!("SELECT {} FROM {} WHERE tag_name = ?1", owner, table))
+                    .map_err(|e| e.to_string())?;
+                let ids = stmt
+                    .query_map([variant], |row| row.get::<_, String>(0))
+                    .map_err(|e| e.to_string())?
+                    .collect::<Result<Vec<String>>>()
+                    .map_err(|e| e.to_string())?;
+                affected.extend(ids);
+                
+                tx.execute(
+                    &format!(
+                        "INSERT OR IGNORE INTO {0} ({1}, tag_name) SELECT {1}, ?1 FROM {0} WHERE tag_name = ?2",
+                        table, owner
+                    ),
+                    (&canonical, variant),
+                )
+                .map_err(|e| e.to_string())?;
+                // Explicit delete: the cascade doesn't fire when foreign keys are off
+                tx.execute(&format!("DELETE FROM {} WHERE tag_name = ?1", table), [variant])
+                    .map_err(|e| e.to_string())?;
+            }
+            
+            tx.execute("DELETE FROM tags WHERE name = ?1", [variant])
+                .map_err(|e| e.to_string())?;
+            write_tombstone(&tx, "tag", variant)?;
+            merged += 1;
+        }
+    }
+    
+    // Bump updated_at so frontend caches pick up the renamed tags
+    for note_id in &affected_notes {
+        tx.execute("UPDATE notes SET updated_at = ?1 WHERE id = ?2", (&now, note_id))
+            .map_err(|e| e.to_string())?;
+    }
+    for folder_id in &affected_folders {
+        tx.execute("UPDATE folders SET updated_at = ?1 WHERE id = ?2", (&now, folder_id))
+            .map_err(|e| e.to_string())?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    Ok(merged)
+}
+
+/// Tags that appear alongside `tag_name` on non-deleted notes, with co-occurrence counts (most first)
+#[tauri::command]
+pub fn get_tag_cooccurrence(tag_name: String, state: State<DbConnection>) -> Result<Vec<(String, i64)>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(
+            "SELECT other.tag_name, COUNT(*) AS together
+             FROM note_tags mine
+             JOIN note_tags other ON other.note_id = mine.note_id AND other.tag_name != mine.tag_name
+             JOIN notes ON notes.id = mine.note_id
+             WHERE mine.tag_name = ?1 AND notes.deleted_at IS NULL
+             GROUP BY other.tag_name
+             ORDER BY together DESC, other.tag_name",
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let counts = stmt
+        .query_map([&tag_name], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<(String, i64)>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(counts)
+}
+
+/// Tag names starting with `prefix` for editor autocomplete, most-used first
+/// Matching is case-insensitive; an empty prefix returns the top `limit` tags overall
+#[tauri::command]
+pub fn get_tag_suggestions(prefix: String, limit: i64, state: State<DbConnection>) -> Result<Vec<String>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // SQLite treats a negative LIMIT as "no limit"
+    if limit < 0 {
+        return Err(DbError::Validation(format!("Invalid limit {}: must not be negative", limit)).into());
+    }
+    
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT tags.name FROM tags
+             LEFT JOIN note_tags ON note_tags.tag_name = tags.name
+             WHERE tags.deleted_at IS NULL AND tags.name LIKE ?1 || '%' ESCAPE '\\'
+             GROUP BY tags.name
+             ORDER BY COUNT(note_tags.note_id) DESC, tags.name COLLATE NOCASE
+             LIMIT ?2",
+        )
+        .map_err(|e| e.to_string())?;
+    
+    // LIKE is already case-insensitive for ASCII in SQLite
+    let names = stmt
+        .query_map((escape_like(prefix.trim()), limit), |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(names)
+}
+
+/// Batch tag lookup for notes already loaded as metadata: note id -> tag names
+/// Every requested id is present; untagged (or unknown) notes map to an empty list
+#[tauri::command]
+pub fn get_tags_for_notes(note_ids: Vec<String>, state: State<DbConnection>) -> Result<HashMap<String, Vec<String>>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut tags_by_note = load_tags_by_note(conn, Some(&note_ids))?;
+    for note_id in note_ids {
+        tags_by_note.entry(note_id).or_default();
+    }
+    
+    Ok(tags_by_note)
+}
+
+/// Tag names on a single note, without loading the note itself (empty when untagged)
+#[tauri::command]
+pub fn get_note_tags(note_id: String, state: State<DbConnection>) -> Result<Vec<String>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare_cached("SELECT tag_name FROM note_tags WHERE note_id = ?1 ORDER BY rowid")
+        .map_err(|e| e.to_string())?;
+    
+    let tags = stmt
+        .query_map([&note_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(tags)
+}
+
+/// Tag names on a single folder, without loading the folder itself (empty when untagged)
+#[tauri::command]
+pub fn get_folder_tags(folder_id: String, state: State<DbConnection>) -> Result<Vec<String>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare_cached("SELECT tag_name FROM folder_tags WHERE folder_id = ?1 ORDER BY rowid")
+        .map_err(|e| e.to_string())?;
+    
+    let tags = stmt
+        .query_map([&folder_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(tags)
+}
+
+/// Characters that continue an inline `#tag` (so `#work` never matches inside `#workshop`)
+fn is_hashtag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Rewrite whole-word `#old` mentions in `text` to `#new`; returns how many were rewritten
+fn rewrite_hashtag(text: &mut String, old: &str, new: &str) -> usize {
+    let needle = format!("#{}", old);
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut hits = 0;
+    for (start, _) in text.match_indices(&needle) {
+        let end = start + needle.len();
+        // Skip `a#old` (not a hashtag) and `#oldsomething` / `#old/child` (a different tag)
+        let starts_word = !text[..start].chars().next_back().is_some_and(|c| is_hashtag_char(c) || c == '#');
+        let ends_word = !text[end..].chars().next().is_some_and(is_hashtag_char);
+        if !starts_word || !ends_word {
+            continue;
+        }
+        out.push_str(&text[last..start]);
+        out.push('#');
+        out.push_str(new);
+        last = end;
+        hits += 1;
+    }
+    if hits > 0 {
+        out.push_str(&text[last..]);
+        *text = out;
+    }
+    hits
+}
+
+/// Rewrite inline `#old` mentions in note content to `#new` (structured tags are left alone)
+/// Runs in one transaction, bumps updated_at on each edited note and returns how many were edited
+#[tauri::command]
+pub fn rewrite_inline_tag(old: String, new: String, state: State<DbConnection>) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let old = old.trim();
+    let new = new.trim();
+    for (label, name) in [("Old", old), ("New", new)] {
+        if name.is_empty() || !name.chars().all(is_hashtag_char) {
+            return Err(DbError::Validation(format!(
+                "{} tag '{}' can't be written as an inline #tag",
+                label, name
+            ))
+            .into());
+        }
+    }
+    if old == new {
+        return Ok(0);
+    }
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    // LIKE is only a prefilter (and case-insensitive); rewrite_hashtag does the exact matching
+    let candidates: Vec<(String, String)> = {
+        let mut stmt = tx
+            .prepare("SELECT id, content FROM notes WHERE is_locked = 0 AND content LIKE ?1 ESCAPE '\\'")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([format!("%#{}%", escape_like(old))], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+    
+    let now = now_timestamp();
+    let mut edited = 0;
+    for (note_id, content) in candidates {
+        let (rewritten, hits) = rewrite_note_text(&content, |text| rewrite_hashtag(text, old, new), true);
+        if hits == 0 {
+            continue;
+        }
+        guard_note_content(&tx, &note_id, &note_id, &rewritten)?;
+        let audit = begin_save_audit(&tx, &note_id)?;
+        tx.execute(
+            "UPDATE notes SET content = ?1, content_hash = ?2, updated_at = ?3 WHERE id = ?4",
+            (&rewritten, content_hash(&rewritten), &now, &note_id),
+        )
+        .map_err(|e| e.to_string())?;
+        record_save_audit(&tx, audit, "rewrite_inline_tag", &note_id, &rewritten)?;
+        edited += 1;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🏷️ Rewrote #{} -> #{} in {} notes", old, new, edited);
+    Ok(edited)
+}
+
+/// One color from get_used_colors and how many active folders / tags use it
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorUsage {
+    pub color: String,
+    pub folder_count: i64,
+    pub tag_count: i64,
+}
+
+/// Every distinct color set on a non-deleted folder or tag, most used first (for the color picker)
+#[tauri::command]
+pub fn get_used_colors(state: State<DbConnection>) -> Result<Vec<ColorUsage>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare(
+            "SELECT color, SUM(is_folder), SUM(1 - is_folder) FROM (
+                 SELECT color, 1 AS is_folder FROM folders WHERE deleted_at IS NULL
+                 UNION ALL
+                 SELECT color, 0 AS is_folder FROM tags WHERE deleted_at IS NULL
+             )
+             WHERE color IS NOT NULL AND color != ''
+             GROUP BY color
+             ORDER BY COUNT(*) DESC, color",
+        )
+        .map_err(|e| e.to_string())?;
+    let colors = stmt
+        .query_map([], |row| {
+            Ok(ColorUsage {
+                color: row.get(0)?,
+                folder_count: row.get(1)?,
+                tag_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<ColorUsage>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(colors)
+}
+
+/// Delete a tag from the database, along with its note_tags / folder_tags rows
+/// Bumps updated_at on every note/folder that carried the tag and returns their ids
+#[tauri::command]
+pub fn delete_tag(tag_name: String, state: State<DbConnection>) -> Result<AffectedIds, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    // Collect affected ids before the junction rows go
+    let collect_ids = |sql: &str| -> Result<Vec<String>, String> {
+        let mut stmt = tx.prepare(sql).map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([&tag_name], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<String>>>()
+            .map_err(|e| e.to_string())?;
+        Ok(ids)
+    };
+    let affected = AffectedIds {
+        note_ids: collect_ids("SELECT note_id FROM note_tags WHERE tag_name = ?1")?,
+        folder_ids: collect_ids("SELECT folder_id FROM folder_tags WHERE tag_name = ?1")?,
+    };
+    
+    // Explicit deletes: the cascade doesn't fire when foreign keys are off
+    tx.execute("DELETE FROM note_tags WHERE tag_name = ?1", [&tag_name])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM folder_tags WHERE tag_name = ?1", [&tag_name])
+        .map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM tags WHERE name = ?1",
+        [&tag_name],
+    )
+    .map_err(|e| e.to_string())?;
+    write_tombstone(&tx, "tag", &tag_name)?;
+    
+    // Bump updated_at so frontend caches drop the stale tag
+    let now = now_timestamp();
+    for note_id in &affected.note_ids {
+        tx.execute("UPDATE notes SET updated_at = ?1 WHERE id = ?2", (&now, note_id))
+            .map_err(|e| e.to_string())?;
+    }
+    for folder_id in &affected.folder_ids {
+        tx.execute("UPDATE folders SET updated_at = ?1 WHERE id = ?2", (&now, folder_id))
+            .map_err(|e| e.to_string())?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!(
+        "🏷️ Tag '{}' deleted ({} notes, {} folders updated)",
+        tag_name,
+        affected.note_ids.len(),
+        affected.folder_ids.len()
+    );
+    
+    Ok(affected)
+}
+
+/// Permanently delete a note from the database
+/// This removes the note record and all associated junction table entries
+#[tauri::command]
+pub fn delete_note_permanently(note_id: String, state: State<DbConnection>) -> Result<String, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    // Delete from notes table (junction table note_tags will cascade delete automatically)
+    tx.execute(
+        "DELETE FROM notes WHERE id = ?1",
+        [&note_id],
+    )
+    .map_err(|e| e.to_string())?;
+    write_tombstone(&tx, "note", &note_id)?;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🗑️ Permanently deleted note: {}", note_id);
+    Ok(format!("Note '{}' permanently deleted", note_id))
+}
+
+/// Permanently delete a folder from the database
+/// This removes the folder record and all associated junction table entries
+/// `orphan_policy` decides what happens to the folder's active notes, in the same transaction:
+/// "reparent_to_root" (default), "reparent_to_parent" or "delete" (move them to the trash).
+/// Notes already in the trash are reparented to root under every policy and aren't counted
+#[tauri::command]
+pub fn delete_folder_permanently(
+    folder_id: String,
+    orphan_policy: Option<String>,
+    state: State<DbConnection>,
+) -> Result<OrphanedNotes, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let policy = orphan_policy.as_deref().unwrap_or("reparent_to_root");
+    if !matches!(policy, "reparent_to_root" | "reparent_to_parent" | "delete") {
+        return Err(DbError::Validation(format!(
+            "Invalid orphan policy '{}': expected reparent_to_root, reparent_to_parent or delete",
+            policy
+        ))
+        .into());
+    }
+    
+    let orphaned = remove_folder(conn, &folder_id, policy)?;
+    
+    println!(
+        "🗑️ Permanently deleted folder: {} ({} notes reparented, {} trashed)",
+        folder_id, orphaned.reparented, orphaned.trashed
+    );
+    Ok(orphaned)
+}
+
+/// delete_folder_permanently's transaction, for an already-validated orphan policy
+fn remove_folder(conn: &mut Connection, folder_id: &str, policy: &str) -> Result<OrphanedNotes, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let parent_id: Option<String> = tx
+        .query_row("SELECT parent_id FROM folders WHERE id = ?1", [folder_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten();
+    
+    // notes.folder_id has no FK, so without this the notes would silently dangle
+    let now = now_timestamp();
+    let trashed = if policy == "delete" {
+        tx.execute(
+            "UPDATE notes SET deleted_at = ?1, updated_at = ?1, folder_id = NULL
+             WHERE folder_id = ?2 AND deleted_at IS NULL",
+            (&now, folder_id),
+        )
+        .map_err(|e| e.to_string())?
+    } else {
+        0
+    };
+    let target = if policy == "reparent_to_parent" { parent_id } else { None };
+    let reparented = tx
+        .execute(
+            "UPDATE notes SET folder_id = ?1, updated_at = ?2 WHERE folder_id = ?3 AND deleted_at IS NULL",
+            (&target, &now, folder_id),
+        )
+        .map_err(|e| e.to_string())?;
+    // Trashed notes restore to root rather than into a folder the user never chose
+    tx.execute(
+        "UPDATE notes SET folder_id = NULL, updated_at = ?1 WHERE folder_id = ?2",
+        (&now, folder_id),
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Update any child folders to remove their parent reference
+    // This prevents foreign key constraint violations
+    tx.execute(
+        "UPDATE folders SET parent_id = NULL WHERE parent_id = ?1",
+        [folder_id],
+    )
+    .map_err(|e| e.to_string())?;
+    
+    // Then delete the folder (junction table folder_tags will cascade delete automatically)
+    tx.execute(
+        "DELETE FROM folders WHERE id = ?1",
+        [folder_id],
+    )
+    .map_err(|e| e.to_string())?;
+    write_tombstone(&tx, "folder", folder_id)?;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    Ok(OrphanedNotes { reparented, trashed })
+}
+
+/// Empty the trash: permanently delete every soft-deleted note and folder in one transaction
+/// Junction rows cascade; each deletion leaves a tombstone for sync
+#[tauri::command]
+pub fn clear_trash(state: State<DbConnection>) -> Result<TrashCleared, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let collect_trashed = |table: &str| -> Result<Vec<String>, String> {
+        let mut stmt = tx
+            .prepare(&format!("SELECT id FROM {} WHERE deleted_at IS NOT NULL", table))
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<String>>>()
+            .map_err(|e| e.to_string())?;
+        Ok(ids)
+    };
+    let note_ids = collect_trashed("notes")?;
+    let folder_ids = collect_trashed("folders")?;
+    
+    tx.execute("DELETE FROM notes WHERE deleted_at IS NOT NULL", [])
+        .map_err(|e| e.to_string())?;
+    
+    // Detach surviving children first to avoid FK violations (same as delete_folder_permanently)
+    tx.execute(
+        "UPDATE folders SET parent_id = NULL
+         WHERE deleted_at IS NULL
+           AND parent_id IN (SELECT id FROM folders WHERE deleted_at IS NOT NULL)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM folder_delete_cascade
+         WHERE root_folder_id IN (SELECT id FROM folders WHERE deleted_at IS NOT NULL)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM folders WHERE deleted_at IS NOT NULL", [])
+        .map_err(|e| e.to_string())?;
+    
+    for note_id in &note_ids {
+        write_tombstone(&tx, "note", note_id)?;
+    }
+    for folder_id in &folder_ids {
+        write_tombstone(&tx, "folder", folder_id)?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🗑️ Emptied trash: {} notes, {} folders", note_ids.len(), folder_ids.len());
+    Ok(TrashCleared {
+        notes_deleted: note_ids.len(),
+        folders_deleted: folder_ids.len(),
+    })
+}
+
+/// Cleanup database on app shutdown (optional but recommended)
+/// Checkpoints WAL to main database to keep files tidy
+#[tauri::command]
+pub fn cleanup_database(state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    // Checkpoint WAL to merge pending writes into main database
+    // PASSIVE mode: Non-blocking, best effort
+    conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |_| Ok(())).ok();
+    
+    let pruned = prune_tombstones(conn)?;
+    if pruned > 0 {
+        println!("🧹 Pruned {} old tombstones", pruned);
+    }
+    
+    Ok("Database cleanup complete".to_string())
+}
+
+/// Maintenance: REINDEX every index, ANALYZE for fresh query planner statistics,
+/// then rebuild the full-text index(es). Can take a while on a large database
+#[tauri::command]
+pub fn reindex_database(state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let started = std::time::Instant::now();
+    
+    conn.execute_batch("REINDEX").map_err(|e| e.to_string())?;
+    conn.execute_batch("ANALYZE").map_err(|e| e.to_string())?;
+    
+    let mut fts_tables = vec!["notes_fts"];
+    if substring_index_enabled(conn)? {
+        fts_tables.push("notes_fts_trigram");
+    }
+    for table in &fts_tables {
+        conn.execute(&format!("INSERT INTO {0}({0}) VALUES ('rebuild')", table), [])
+            .map_err(|e| e.to_string())?;
+    }
+    
+    let summary = format!(
+        "Reindexed, analyzed and rebuilt {} in {} ms",
+        fts_tables.join(", "),
+        started.elapsed().as_millis()
+    );
+    println!("🧰 {}", summary);
+    Ok(summary)
+}
+
+/// Outcome of is_database_healthy; `issues` is empty when `healthy`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthCheck {
+    pub healthy: bool,
+    pub issues: Vec<String>,
+}
+
+// Tables init_database creates and everything else relies on
+const EXPECTED_TABLES: &[&str] = &[
+    "notes",
+    "folders",
+    "tags",
+    "note_tags",
+    "folder_tags",
+    "settings",
+    "folder_delete_cascade",
+    "tombstones",
+    "save_audit",
+    "templates",
+    "notes_fts",
+];
+
+/// Fast startup sanity check: PRAGMA quick_check, expected tables (including the FTS index)
+/// and foreign key enforcement. A missing notes_fts is reported as its own issue
+#[tauri::command]
+pub fn is_database_healthy(state: State<DbConnection>) -> Result<HealthCheck, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut issues = Vec::new();
+    
+    // quick_check skips index cross-checks, so it stays fast on large databases
+    let mut stmt = conn.prepare("PRAGMA quick_check(20)").map_err(|e| e.to_string())?;
+    let problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>>>()
+        .map_err(|e| e.to_string())?;
+    issues.extend(
+        problems
+            .into_iter()
+            .filter(|line| line != "ok")
+            .map(|line| format!("quick_check: {}", line)),
+    );
+    
+    let mut expected: Vec<&str> = EXPECTED_TABLES.to_vec();
+    if substring_index_enabled(conn)? {
+        expected.push("notes_fts_trigram");
+    }
+    let mut table_exists = conn
+        .prepare("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)")
+        .map_err(|e| e.to_string())?;
+    for table in expected {
+        let exists: bool = table_exists
+            .query_row([table], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if exists {
+            continue;
+        }
+        if table.starts_with("notes_fts") {
+            issues.push(format!("Search index table '{}' is missing", table));
+        } else {
+            issues.push(format!("Table '{}' is missing", table));
+        }
+    }
+    
+    let foreign_keys: bool = conn
+        .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if !foreign_keys {
+        issues.push("Foreign key enforcement is off".to_string());
+    }
+    
+    if !issues.is_empty() {
+        println!("⚠️ Database health check found {} issues", issues.len());
+    }
+    Ok(HealthCheck { healthy: issues.is_empty(), issues })
+}
+
+/// Result of a manual WAL checkpoint (mirrors `PRAGMA wal_checkpoint`'s row)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointResult {
+    /// True when a concurrent reader/writer kept the checkpoint from completing
+    pub busy: bool,
+    pub log_frames: i64,
+    pub checkpointed_frames: i64,
+}
+
+/// Run a WAL checkpoint on demand: "PASSIVE", "FULL", "RESTART" or "TRUNCATE"
+/// cleanup_database only ever does a PASSIVE one
+#[tauri::command]
+pub fn checkpoint_now(mode: String, state: State<DbConnection>) -> Result<CheckpointResult, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let mode = match mode.trim().to_uppercase().as_str() {
+        "PASSIVE" => "PASSIVE",
+        "FULL" => "FULL",
+        "RESTART" => "RESTART",
+        "TRUNCATE" => "TRUNCATE",
+        _ => {
+            return Err(DbError::Validation(format!(
+                "Invalid checkpoint mode '{}': expected PASSIVE, FULL, RESTART or TRUNCATE",
+                mode
+            ))
+            .into())
+        }
+    };
+    
+    let result = conn
+        .query_row(&format!("PRAGMA wal_checkpoint({})", mode), [], |row| {
+            Ok(CheckpointResult {
+                busy: row.get::<_, i64>(0)? != 0,
+                log_frames: row.get(1)?,
+                checkpointed_frames: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    
+    println!("🧾 WAL checkpoint ({}): {:?}", mode, result);
+    Ok(result)
+}
+
+/// Copy the live database to `dest_path` (e.g. before a risky operation)
+/// Uses SQLite's online backup API, so an in-flight write can't leave a torn copy
+#[tauri::command]
+pub fn backup_database(dest_path: String, state: State<DbConnection>) -> Result<BackupInfo, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    prepare_db_path(&dest_path)?;
+    
+    // TRUNCATE: fold the WAL into the main file first
+    if !conn.is_readonly(DatabaseName::Main).map_err(|e| e.to_string())? {
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| e.to_string())?;
+    }
+    
+    conn.backup(DatabaseName::Main, &dest_path, None)
+        .map_err(|e| e.to_string())?;
+    
+    let size_bytes = std::fs::metadata(&dest_path)
+        .map_err(|e| e.to_string())?
+        .len();
+    
+    println!("💾 Database backed up to {} ({} bytes)", dest_path, size_bytes);
+    
+    Ok(BackupInfo { path: dest_path, size_bytes })
+}
+
+/// Checkpoint the WAL and drop the connection (vault switching, deleting the file)
+/// Every other command returns NotInitialized until init_database runs again
+#[tauri::command]
+pub fn close_database(state: State<DbConnection>) -> Result<String, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.take().ok_or(DbError::NotInitialized)?;
+    
+    // TRUNCATE: fold the WAL back in and leave no -wal file behind
+    if !conn.is_readonly(DatabaseName::Main).map_err(|e| e.to_string())? {
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| e.to_string())?;
+    }
+    
+    drop(conn);
+    println!("🔌 Database closed");
+    
+    Ok("Database closed".to_string())
+}
+
+/// What probe_database could tell about a file without opening it for real
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbProbe {
+    /// Plain SQLite database (header matches and it can be read)
+    pub valid: bool,
+    /// Looks like an SQLCipher database (page-aligned, no SQLite header, unreadable without a key)
+    pub encrypted: bool,
+    /// `PRAGMA user_version` when readable
+    pub schema_version: Option<i64>,
+    /// Rows in `notes` when readable and the table exists
+    pub note_count: Option<i64>,
+}
+
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Inspect a database file before init_database: plain SQLite, encrypted, or garbage
+/// Opens read-only and never creates, migrates or modifies the file
+#[tauri::command]
+pub fn probe_database(path: String) -> Result<DbProbe, String> {
+    let file_path = Path::new(&path);
+    if !file_path.is_file() {
+        return Err(DbError::InvalidPath(path).into());
+    }
+    
+    let len = std::fs::metadata(file_path)
+        .map_err(|source| DbError::Io { path: path.clone(), source })?
+        .len();
+    let mut header = [0u8; 16];
+    let mut file = std::fs::File::open(file_path).map_err(|source| DbError::Io { path: path.clone(), source })?;
+    let has_header = file.read_exact(&mut header).is_ok() && &header == SQLITE_HEADER;
+    drop(file);
+    
+    let conn = Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| e.to_string())?;
+    
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+        // An empty file is a valid (empty) database to SQLite too
+        Ok(_) if has_header || len == 0 => {
+            let schema_version = conn
+                .query_row("PRAGMA user_version", [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            let note_count = conn
+                .query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))
+                .ok();
+            Ok(DbProbe { valid: true, encrypted: false, schema_version: Some(schema_version), note_count })
+        }
+        Ok(_) => Ok(DbProbe { valid: false, encrypted: false, schema_version: None, note_count: None }),
+        // Without the key SQLCipher output is indistinguishable from noise; page alignment is the best hint
+        Err(rusqlite::Error::SqliteFailure(err, _)) if err.code == rusqlite::ErrorCode::NotADatabase => {
+            Ok(DbProbe {
+                valid: false,
+                encrypted: !has_header && len >= 1024 && len % 512 == 0,
+                schema_version: None,
+                note_count: None,
+            })
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Save a single UI state key-value pair
+#[tauri::command]
+pub fn save_ui_state(key: String, value: String, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    put_setting(conn, &key, &value)?;
+    
+    Ok(format!("UI state saved: {}", key))
+}
+
+/// Load a single UI state value by key
+#[tauri::command]
+pub fn load_ui_state(key: String, state: State<DbConnection>) -> Result<Option<String>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let value = conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            [&key],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(value)
+}
+
+/// Save a boolean UI state value (stored as JSON `true`/`false`)
+#[tauri::command]
+pub fn save_ui_state_bool(key: String, value: bool, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    put_setting(conn, &key, &value.to_string())?;
+    
+    Ok(format!("UI state saved: {}", key))
+}
+
+/// Load a boolean UI state value
+#[tauri::command]
+pub fn load_ui_state_bool(key: String, state: State<DbConnection>) -> Result<Option<bool>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    get_setting_as(conn, &key)
+}
+
+/// Save an integer UI state value
+#[tauri::command]
+pub fn save_ui_state_int(key: String, value: i64, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    put_setting(conn, &key, &value.to_string())?;
+    
+    Ok(format!("UI state saved: {}", key))
+}
+
+/// Load an integer UI state value
+#[tauri::command]
+pub fn load_ui_state_int(key: String, state: State<DbConnection>) -> Result<Option<i64>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    get_setting_as(conn, &key)
+}
+
+/// Save an arbitrary JSON UI state value
+#[tauri::command]
+pub fn save_ui_state_json(key: String, value: serde_json::Value, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let encoded = serde_json::to_string(&value).map_err(|e| e.to_string())?;
+    put_setting(conn, &key, &encoded)?;
+    
+    Ok(format!("UI state saved: {}", key))
+}
+
+/// Load a JSON UI state value
+#[tauri::command]
+pub fn load_ui_state_json(key: String, state: State<DbConnection>) -> Result<Option<serde_json::Value>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    get_setting_as(conn, &key)
+}
+
+/// Load all UI state settings (keys starting with 'ui.')
+#[tauri::command]
+pub fn load_all_ui_state(state: State<DbConnection>) -> Result<HashMap<String, String>, String> {
+    load_settings_by_prefix("ui.".to_string(), state)
+}
+
+/// Load every setting whose key starts with `prefix` (e.g. "window.", "editor.")
+/// `%` / `_` in the prefix match literally
+#[tauri::command]
+pub fn load_settings_by_prefix(prefix: String, state: State<DbConnection>) -> Result<HashMap<String, String>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let mut stmt = conn
+        .prepare_cached("SELECT key, value FROM settings WHERE key LIKE ?1 || '%' ESCAPE '\\'")
+        .map_err(|e| e.to_string())?;
+    
+    let rows = stmt
+        .query_map([escape_like(&prefix)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    
+    let mut settings = HashMap::new();
+    for result in rows {
+        let (key, value) = result.map_err(|e| e.to_string())?;
+        settings.insert(key, value);
+    }
+    
+    Ok(settings)
+}
+
+/// Delete a single setting; returns 1 if it existed, 0 otherwise
+#[tauri::command]
+pub fn delete_setting(key: String, state: State<DbConnection>) -> Result<usize, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let deleted = conn
+        .execute("DELETE FROM settings WHERE key = ?1", [&key])
+        .map_err(|e| e.to_string())?;
+    
+    Ok(deleted)
+}
+
+/// Delete every setting whose key starts with `prefix` ("reset editor preferences")
+/// An empty prefix is rejected so a bad call can't wipe internal settings too
+#[tauri::command]
+pub fn delete_settings_by_prefix(prefix: String, state: State<DbConnection>) -> Result<usize, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    if prefix.is_empty() {
+        return Err(DbError::Validation("Settings prefix cannot be empty".to_string()).into());
+    }
+    
+    let deleted = conn
+        .execute(
+            "DELETE FROM settings WHERE key LIKE ?1 || '%' ESCAPE '\\'",
+            [escape_like(&prefix)],
+        )
+        .map_err(|e| e.to_string())?;
+    
+    println!("🧹 Deleted {} settings under '{}'", deleted, prefix);
+    Ok(deleted)
+}
+
+/// Aggregate counts for the dashboard
+/// A handful of aggregate queries - cheap enough to call on app open
+#[tauri::command]
+pub fn get_database_stats(state: State<DbConnection>) -> Result<DbStats, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // Active / trashed / favorite note counts in a single pass
+    let (total_notes, deleted_notes, favorites) = conn
+        .query_row(
+            "SELECT 
+                COALESCE(SUM(CASE WHEN deleted_at IS NULL THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN deleted_at IS NOT NULL THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN deleted_at IS NULL AND is_favorite = 1 THEN 1 ELSE 0 END), 0)
+             FROM notes",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let total_folders: i64 = conn
+        .query_row("SELECT COUNT(*) FROM folders WHERE deleted_at IS NULL", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    
+    let total_tags: i64 = conn
+        .query_row("SELECT COUNT(*) FROM tags WHERE deleted_at IS NULL", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    
+    // Tag histogram (soft-deleted notes excluded)
+    let mut stmt = conn
+        .prepare(
+            "SELECT note_tags.tag_name, COUNT(*) 
+             FROM note_tags
+             JOIN notes ON notes.id = note_tags.note_id
+             WHERE notes.deleted_at IS NULL
+             GROUP BY note_tags.tag_name"
+        )
+        .map_err(|e| e.to_string())?;
+    
+    let notes_per_tag = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<String, i64>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(DbStats {
+        total_notes,
+        deleted_notes,
+        total_folders,
+        total_tags,
+        favorites,
+        notes_per_tag,
+    })
+}
+
+/// Change the SQLCipher passphrase of the open database
+/// The old passphrase is verified against the file before re-keying
+#[tauri::command]
+pub fn change_passphrase(old: String, new: String, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    if new.is_empty() {
+        return Err("New passphrase must not be empty".to_string());
+    }
+    
+    // Verify the old passphrase on a separate connection to the same file
+    let db_path = conn
+        .path()
+        .filter(|p| !p.is_empty())
+        .ok_or("Cannot change passphrase of an in-memory database")?
+        .to_string();
+    let verify_conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    apply_passphrase(&verify_conn, &old)?;
+    drop(verify_conn);
+    
+    // SQLCipher can't rekey in WAL mode: fold the WAL back in, rekey, then restore the mode
+    let journal_mode: String = conn
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(())).ok();
+    conn.query_row("PRAGMA journal_mode = DELETE", [], |_| Ok(()))
+        .map_err(|e| e.to_string())?;
+    
+    let rekey_result = conn.pragma_update(None, "rekey", &new);
+    
+    conn.query_row(&format!("PRAGMA journal_mode = {}", journal_mode), [], |_| Ok(()))
+        .map_err(|e| e.to_string())?;
+    rekey_result.map_err(|e| e.to_string())?;
+    
+    Ok("Passphrase changed".to_string())
+}
+
+/// Non-deleted notes whose created or updated time falls within [start, end] (inclusive)
+/// `field`: "created" or "updated"; bounds are RFC3339 timestamps or whole YYYY-MM-DD days,
+/// normalized to the stored form so they compare as text
+#[tauri::command]
+pub fn get_notes_in_date_range(
+    start: String,
+    end: String,
+    field: String,
+    state: State<DbConnection>,
+) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // Allowlisted column; plain comparison keeps idx_notes_updated usable
+    let column = match field.as_str() {
+        "created" => "created_at",
+        "updated" => "updated_at",
+        other => return Err(format!("Invalid field '{}': expected created or updated", other)),
+    };
+    let start = normalize_range_bound("start", &start, false)?;
+    let end = normalize_range_bound("end", &end, true)?;
+    if start > end {
+        return Err(DbError::Validation(format!("Invalid range: start {} is after end {}", start, end)).into());
+    }
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {cols} FROM notes
+             WHERE {column} BETWEEN ?1 AND ?2 AND deleted_at IS NULL
+             ORDER BY {column} DESC",
+            cols = NOTE_COLUMNS,
+            column = column,
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map((&start, &end), note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+/// Per-day counts of non-deleted notes for an activity heatmap: "YYYY-MM-DD" (UTC) -> count
+/// `start` / `end` are inclusive YYYY-MM-DD days; `field` is "created" (default) or "updated".
+/// Days without notes are absent from the map
+#[tauri::command]
+pub fn get_activity_counts(
+    start: String,
+    end: String,
+    field: Option<String>,
+    state: State<DbConnection>,
+) -> Result<HashMap<String, i64>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let column = match field.as_deref().unwrap_or("created") {
+        "created" => "created_at",
+        "updated" => "updated_at",
+        other => return Err(format!("Invalid field '{}': expected created or updated", other)),
+    };
+    let parse_day = |label: &str, value: &str| {
+        chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| DbError::Validation(format!("Invalid {} '{}': expected YYYY-MM-DD", label, value)))
+    };
+    let first = parse_day("start", &start)?;
+    let last = parse_day("end", &end)?;
+    let after_last = last
+        .succ_opt()
+        .ok_or_else(|| DbError::Validation(format!("Invalid end '{}': out of range", end)))?;
+    
+    // Half-open text range on the stored timestamps keeps the column's index usable
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT date({column}) AS day, COUNT(*) FROM notes
+             WHERE {column} >= ?1 AND {column} < ?2 AND deleted_at IS NULL
+             GROUP BY day",
+            column = column,
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let counts = stmt
+        .query_map(
+            (first.format("%Y-%m-%d").to_string(), after_last.format("%Y-%m-%d").to_string()),
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<String, i64>>>()
+        .map_err(|e| e.to_string())?;
+    
+    Ok(counts)
+}
+
+/// Load notes matching a fixed WHERE clause, newest first, with tags populated
+fn load_notes_where(conn: &Connection, where_clause: &str) -> Result<Vec<Note>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM notes WHERE {} ORDER BY updated_at DESC",
+            NOTE_COLUMNS, where_clause
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map([], note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+/// The `limit` most recently edited non-deleted notes (startup "recent" list)
+#[tauri::command]
+pub fn get_recent_notes(limit: i64, state: State<DbConnection>) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    if limit < 0 {
+        return Err(DbError::Validation(format!("Invalid limit {}: must not be negative", limit)).into());
+    }
+    
+    let mut stmt = conn
+        .prepare_cached(&format!(
+            "SELECT {} FROM notes WHERE deleted_at IS NULL ORDER BY updated_at DESC LIMIT ?1",
+            NOTE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut notes: Vec<Note> = stmt
+        .query_map([limit], note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+/// Non-deleted favorite notes and folders in one call, newest first
+#[tauri::command]
+pub fn get_favorites(state: State<DbConnection>) -> Result<Favorites, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let notes = load_notes_where(conn, "is_favorite = 1 AND deleted_at IS NULL")?;
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM folders WHERE is_favorite = 1 AND deleted_at IS NULL ORDER BY updated_at DESC",
+            FOLDER_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut folders: Vec<Folder> = stmt
+        .query_map([], folder_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Folder>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_folder_tags(conn, &mut folders)?;
+    
+    Ok(Favorites { notes, folders })
+}
+
+/// Previous/next note ids around `note_id` in updated_at DESC order (prev = newer)
+/// Scoped to `folder_id` when given, otherwise across all non-deleted notes
+#[tauri::command]
+pub fn get_note_neighbors(
+    note_id: String,
+    folder_id: Option<String>,
+    state: State<DbConnection>,
+) -> Result<Neighbors, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    conn.query_row(
+        "WITH ordered AS (
+             SELECT id,
+                    LAG(id) OVER w AS prev_id,
+                    LEAD(id) OVER w AS next_id
+             FROM notes
+             WHERE deleted_at IS NULL AND (?2 IS NULL OR folder_id = ?2)
+             WINDOW w AS (ORDER BY updated_at DESC, id)
+         )
+         SELECT prev_id, next_id FROM ordered WHERE id = ?1",
+        (&note_id, &folder_id),
+        |row| Ok(Neighbors { prev: row.get(0)?, next: row.get(1)? }),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Note '{}' not found in this list", note_id))
+}
+
+/// Other non-deleted notes sharing the most tags with `note_id` ("related notes")
+/// Ordered by shared tag count (most first), then most recently updated
+#[tauri::command]
+pub fn get_related_notes(note_id: String, limit: i64, state: State<DbConnection>) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // SQLite treats a negative LIMIT as "no limit"
+    if limit < 0 {
+        return Err(DbError::Validation(format!("Invalid limit {}: must not be negative", limit)).into());
+    }
+    
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM notes
+             JOIN (
+                 SELECT other.note_id, COUNT(*) AS shared
+                 FROM note_tags mine
+                 JOIN note_tags other ON other.tag_name = mine.tag_name AND other.note_id != mine.note_id
+                 WHERE mine.note_id = ?1
+                 GROUP BY other.note_id
+             ) related ON related.note_id = notes.id
+             WHERE notes.deleted_at IS NULL
+             ORDER BY related.shared DESC, notes.updated_at DESC
+             LIMIT ?2",
+            NOTE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map((&note_id, limit), note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+/// Non-deleted notes not updated in the last `days` days, oldest first (review prompts)
+/// Daily notes are one-off by design, so `exclude_daily` leaves them out
+#[tauri::command]
+pub fn get_stale_notes(
+    days: i64,
+    limit: i64,
+    exclude_daily: bool,
+    state: State<DbConnection>,
+) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    if days < 0 {
+        return Err(DbError::Validation(format!("Invalid days {}: must not be negative", days)).into());
+    }
+    if limit < 0 {
+        return Err(DbError::Validation(format!("Invalid limit {}: must not be negative", limit)).into());
+    }
+    let cutoff = chrono::Duration::try_days(days)
+        .and_then(|age| chrono::Utc::now().checked_sub_signed(age))
+        .map(format_timestamp)
+        .ok_or_else(|| DbError::Validation(format!("Invalid days {}: out of range", days)))?;
+    
+    // Stored timestamps are canonical, so a plain comparison is correct and keeps idx_notes_updated usable
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM notes
+             WHERE updated_at < ?1 AND deleted_at IS NULL
+               AND (?2 = 0 OR daily_note_date IS NULL)
+             ORDER BY updated_at ASC
+             LIMIT ?3",
+            NOTE_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map((&cutoff, exclude_daily as i32, limit), note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+// Stored timestamps are canonical (see backfill_canonical_timestamps), so cursors compare as
+// plain text and notes can use idx_notes_updated; `since` is normalized to the same form first
+const MODIFIED_SINCE_CLAUSE: &str = "updated_at > ?1 ORDER BY updated_at ASC";
+
+/// Notes changed after `since` (RFC3339), oldest change first, for incremental sync
+/// Includes soft-deleted notes so trashing propagates; pass back the max updated_at seen
+#[tauri::command]
+pub fn get_notes_modified_since(since: String, state: State<DbConnection>) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    let since = normalize_timestamp("since", &since)?;
+    
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM notes WHERE {}", NOTE_COLUMNS, MODIFIED_SINCE_CLAUSE))
+        .map_err(|e| e.to_string())?;
+    
+    let mut notes: Vec<Note> = stmt
+        .query_map([&since], note_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Note>>>()
+        .map_err(|e| e.to_string())?;
+    
+    populate_note_tags(conn, &mut notes)?;
+    
+    Ok(notes)
+}
+
+/// Folders changed after `since` (see get_notes_modified_since)
+#[tauri::command]
+pub fn get_folders_modified_since(since: String, state: State<DbConnection>) -> Result<Vec<Folder>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    let since = normalize_timestamp("since", &since)?;
+    
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM folders WHERE {}", FOLDER_COLUMNS, MODIFIED_SINCE_CLAUSE))
+        .map_err(|e| e.to_string())?;
+    
+    let mut folders: Vec<Folder> = stmt
+        .query_map([&since], folder_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Folder>>>()
         .map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_favorite ON notes(is_favorite)", [])
+    
+    populate_folder_tags(conn, &mut folders)?;
+    
+    Ok(folders)
+}
+
+/// Tag metadata changed after `since` (see get_notes_modified_since)
+#[tauri::command]
+pub fn get_tags_modified_since(since: String, state: State<DbConnection>) -> Result<Vec<Tag>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    let since = normalize_timestamp("since", &since)?;
+    
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM tags WHERE {}", TAG_COLUMNS, MODIFIED_SINCE_CLAUSE))
         .map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_notes_updated ON notes(updated_at)", [])
+    
+    let tags = stmt
+        .query_map([&since], tag_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Tag>>>()
         .map_err(|e| e.to_string())?;
     
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_folders_parent ON folders(parent_id)", [])
+    Ok(tags)
+}
+
+/// Permanent deletes recorded after `since`, oldest first, so a sync layer can replay them
+#[tauri::command]
+pub fn get_tombstones_since(since: String, state: State<DbConnection>) -> Result<Vec<Tombstone>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    let since = normalize_timestamp("since", &since)?;
+    
+    let mut stmt = conn
+        .prepare(
+            "SELECT entity_type, entity_id, deleted_at FROM tombstones
+             WHERE deleted_at > ?1
+             ORDER BY deleted_at ASC",
+        )
         .map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_folders_deleted ON folders(deleted_at)", [])
+    
+    let tombstones = stmt
+        .query_map([&since], |row| {
+            Ok(Tombstone {
+                entity_type: row.get(0)?,
+                entity_id: row.get(1)?,
+                deleted_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Tombstone>>>()
         .map_err(|e| e.to_string())?;
     
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_note_tags_note ON note_tags(note_id)", [])
+    Ok(tombstones)
+}
+
+/// (linkType, targetId, label) of every noteLink node in a TipTap document, in document order
+fn collect_note_links(node: &serde_json::Value, links: &mut Vec<(String, String, String)>) {
+    if node.get("type").and_then(|t| t.as_str()) == Some("noteLink") {
+        let attrs = node.get("attrs");
+        let attr = |name: &str| attrs.and_then(|a| a.get(name)).and_then(|v| v.as_str());
+        if let Some(target_id) = attr("targetId") {
+            links.push((
+                attr("linkType").unwrap_or("note").to_string(),
+                target_id.to_string(),
+                attr("label").unwrap_or_default().to_string(),
+            ));
+        }
+    }
+    if let Some(children) = node.get("content").and_then(|c| c.as_array()) {
+        for child in children {
+            collect_note_links(child, links);
+        }
+    }
+}
+
+/// noteLinks in active notes whose target note/folder is in the trash or doesn't exist
+/// Links live inside note content (there's no links table), so every linking note is parsed
+#[tauri::command]
+pub fn get_broken_links(state: State<DbConnection>) -> Result<Vec<BrokenLink>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    // Locked notes only hold the placeholder, so their links can't be checked
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, content FROM notes
+             WHERE deleted_at IS NULL AND is_locked = 0 AND content LIKE '%\"noteLink\"%'
+             ORDER BY created_at",
+        )
         .map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_note_tags_tag ON note_tags(tag_name)", [])
+    let sources = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>>>()
         .map_err(|e| e.to_string())?;
     
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_folder_tags_folder ON folder_tags(folder_id)", [])
+    let mut broken = Vec::new();
+    for (source_note_id, content) in sources {
+        let doc = match serde_json::from_str::<serde_json::Value>(&content) {
+            Ok(doc) => doc,
+            Err(_) => continue,
+        };
+        let mut links = Vec::new();
+        collect_note_links(&doc, &mut links);
+        
+        for (link_type, target_id, label) in links {
+            let table = if link_type == "folder" { "folders" } else { "notes" };
+            let target_deleted_at: Option<Option<String>> = conn
+                .prepare_cached(&format!("SELECT deleted_at FROM {} WHERE id = ?1", table))
+                .map_err(|e| e.to_string())?
+                .query_row([&target_id], |row| row.get(0))
+                .optional()
+                .map_err(|e| e.to_string())?;
+            let reason = match target_deleted_at {
+                None => "missing",
+                Some(Some(_)) => "trashed",
+                Some(None) => continue,
+            };
+            broken.push(BrokenLink {
+                source_note_id: source_note_id.clone(),
+                link_type,
+                target_id,
+                label,
+                reason: reason.to_string(),
+            });
+        }
+    }
+    
+    Ok(broken)
+}
+
+// Notes whose folder is gone. Daily notes live in the virtual DAILY_NOTES_FOLDER_ID folder,
+// which never has a folders row, so they are not orphans
+const ORPHANED_NOTES_CLAUSE: &str = "folder_id IS NOT NULL AND folder_id != '__daily_notes__'
+     AND folder_id NOT IN (SELECT id FROM folders)";
+
+/// Notes whose folder_id points at a folder that no longer exists
+/// (left behind by permanent folder deletes, which don't cascade to notes)
+#[tauri::command]
+pub fn get_orphaned_notes(state: State<DbConnection>) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    load_notes_where(conn, ORPHANED_NOTES_CLAUSE)
+}
+
+/// Non-deleted notes with no tags at all (inbox / needs-review list)
+#[tauri::command]
+pub fn get_untagged_notes(state: State<DbConnection>) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    load_notes_where(
+        conn,
+        "deleted_at IS NULL AND NOT EXISTS (SELECT 1 FROM note_tags WHERE note_tags.note_id = notes.id)",
+    )
+}
+
+/// Non-deleted notes that sit at the root (no folder)
+#[tauri::command]
+pub fn get_notes_without_folder(state: State<DbConnection>) -> Result<Vec<Note>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    load_notes_where(conn, "deleted_at IS NULL AND folder_id IS NULL")
+}
+
+/// One-time sweep of junction rows pointing at missing notes, folders or tags
+/// (data written before foreign keys were enforced)
+#[tauri::command]
+pub fn cleanup_orphaned_relationships(state: State<DbConnection>) -> Result<RelationshipCleanup, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let sweep = |sql: &str| tx.execute(sql, []).map_err(|e| e.to_string());
+    let summary = RelationshipCleanup {
+        note_tags_without_note: sweep("DELETE FROM note_tags WHERE note_id NOT IN (SELECT id FROM notes)")?,
+        folder_tags_without_folder: sweep("DELETE FROM folder_tags WHERE folder_id NOT IN (SELECT id FROM folders)")?,
+        note_tags_without_tag: sweep("DELETE FROM note_tags WHERE tag_name NOT IN (SELECT name FROM tags)")?,
+        folder_tags_without_tag: sweep("DELETE FROM folder_tags WHERE tag_name NOT IN (SELECT name FROM tags)")?,
+    };
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🧹 Removed orphaned relationships: {:?}", summary);
+    
+    Ok(summary)
+}
+
+/// Move every orphaned note into `target_folder_id` (or the root when None)
+/// Returns the number of notes reassigned
+#[tauri::command]
+pub fn reassign_orphaned_notes(target_folder_id: Option<String>, state: State<DbConnection>) -> Result<usize, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    if let Some(target) = &target_folder_id {
+        let exists = conn
+            .query_row("SELECT 1 FROM folders WHERE id = ?1", [target], |_| Ok(()))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .is_some();
+        if !exists {
+            return Err(format!("Target folder '{}' not found", target));
+        }
+    }
+    
+    let now = now_timestamp();
+    let reassigned = conn
+        .execute(
+            &format!("UPDATE notes SET folder_id = ?1, updated_at = ?2 WHERE {}", ORPHANED_NOTES_CLAUSE),
+            (&target_folder_id, &now),
+        )
         .map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_folder_tags_tag ON folder_tags(tag_name)", [])
+    
+    println!("📦 Reassigned {} orphaned notes", reassigned);
+    Ok(reassigned)
+}
+
+/// Move many notes into `target_folder_id` (or the root when None) with a single UPDATE
+/// Returns the number of notes moved; unknown ids are skipped
+#[tauri::command]
+pub fn bulk_move_notes(
+    note_ids: Vec<String>,
+    target_folder_id: Option<String>,
+    state: State<DbConnection>,
+) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    if note_ids.is_empty() {
+        return Ok(0);
+    }
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    // A trashed target would hide the moved notes along with it
+    if let Some(target) = &target_folder_id {
+        if !ensure_parent_not_trashed(&tx, target)? {
+            return Err(format!("Target folder '{}' not found", target));
+        }
+    }
+    
+    let now = now_timestamp();
+    let placeholders = note_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&target_folder_id, &now];
+    params.extend(note_ids.iter().map(|id| id as &dyn rusqlite::ToSql));
+    
+    let moved = tx
+        .execute(
+            &format!("UPDATE notes SET folder_id = ?, updated_at = ? WHERE id IN ({})", placeholders),
+            params.as_slice(),
+        )
         .map_err(|e| e.to_string())?;
     
-    // Create FTS5 virtual table for full-text search (Apple Notes / Bear approach)
-    conn.execute(
-        "CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(
-            note_id UNINDEXED,
-            title,
-            content,
-            tokenize='unicode61'
-        )",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
     
-    // Triggers to keep FTS in sync with notes table
-    // Insert trigger
-    conn.execute(
-        "CREATE TRIGGER IF NOT EXISTS notes_fts_insert AFTER INSERT ON notes BEGIN
-            INSERT INTO notes_fts(note_id, title, content)
-            VALUES (new.id, new.title, new.content);
-        END",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+    println!("📦 Bulk moved {} notes", moved);
+    Ok(moved)
+}
+
+/// Add `tag_name` to many notes in one transaction (creating the tag if needed)
+/// Returns the number of notes that gained the tag; their updated_at is bumped
+#[tauri::command]
+pub fn bulk_add_tag(note_ids: Vec<String>, tag_name: String, state: State<DbConnection>) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
     
-    // Update trigger
-    conn.execute(
-        "CREATE TRIGGER IF NOT EXISTS notes_fts_update AFTER UPDATE ON notes BEGIN
-            UPDATE notes_fts 
-            SET title = new.title, content = new.content
-            WHERE note_id = old.id;
-        END",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+    let tag_name = normalize_tag_name(&tag_name, lowercase_tags(conn)?);
+    if tag_name.is_empty() {
+        return Err(DbError::Validation("Tag name cannot be empty".to_string()).into());
+    }
     
-    // Delete trigger
-    conn.execute(
-        "CREATE TRIGGER IF NOT EXISTS notes_fts_delete AFTER DELETE ON notes BEGIN
-            DELETE FROM notes_fts WHERE note_id = old.id;
-        END",
-        [],
+    let now = now_timestamp();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    tx.execute(
+        "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at)
+         VALUES (?1, '', 1, 0, NULL, ?2, ?2)
+         ON CONFLICT(name) DO NOTHING",
+        (&tag_name, &now),
     )
     .map_err(|e| e.to_string())?;
     
-    // Store connection in state
-    *state.0.lock().unwrap() = Some(conn);
+    let mut changed = 0;
+    {
+        // Select from notes so unknown ids are skipped instead of tripping the FK
+        let mut insert_tag = tx
+            .prepare("INSERT OR IGNORE INTO note_tags (note_id, tag_name) SELECT id, ?2 FROM notes WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+        let mut touch = tx
+            .prepare("UPDATE notes SET updated_at = ?2 WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+        for note_id in &note_ids {
+            if insert_tag.execute((note_id, &tag_name)).map_err(|e| e.to_string())? > 0 {
+                touch.execute((note_id, &now)).map_err(|e| e.to_string())?;
+                changed += 1;
+            }
+        }
+    }
     
-    Ok(format!("Database initialized at: {}", db_path))
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🏷️ Added tag '{}' to {} notes", tag_name, changed);
+    Ok(changed)
 }
 
-/// Save or update a note
+/// Copy a note into `target_folder_id` (root when None) as `new_id`, keeping the original
+/// Content and tags are copied; the copy records its source in `copied_from`
 #[tauri::command]
-pub fn save_note(note: Note, state: State<DbConnection>) -> Result<String, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+pub fn copy_note_to_folder(
+    note_id: String,
+    target_folder_id: Option<String>,
+    new_id: String,
+    state: State<DbConnection>,
+) -> Result<Note, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
     
-    // 🔍 DEBUG: Log content length to catch empty saves
-    println!(
-        "💾 Saving note {} | title: {} | content length: {}",
-        &note.id[..20],  // First 20 chars of ID
-        if note.title.len() > 30 { &note.title[..30] } else { &note.title },
-        note.content.len()
-    );
+    let new_id = new_id.trim().to_string();
+    validate_note_id(&new_id)?;
     
-    // 🛡️ GUARD: Only prevent PURE boot state (null, empty string, etc.)
-    // Allow structured empty content (intentional deletions)
-    let is_pure_boot_state = note.content.is_empty() 
-        || note.content == r#""""# 
-        || note.content == "{}";
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
     
-    if is_pure_boot_state {
-        // Check if note exists in DB with content
-        let existing_content_len: Option<usize> = conn
-            .query_row(
-                "SELECT LENGTH(content) FROM notes WHERE id = ?1",
-                [&note.id],
-                |row| row.get(0)
-            )
-            .ok();
-        
-        // Only block if overwriting existing content with pure boot state
-        if let Some(existing_len) = existing_content_len {
-            if existing_len > 200 {
-                return Err(format!(
-                    "🚨 BLOCKED: Attempted to overwrite note '{}' ({} chars) with pure boot state",
-                    note.title, existing_len
-                ));
+    if let Some(target) = &target_folder_id {
+        let target_deleted_at: Option<Option<String>> = tx
+            .query_row("SELECT deleted_at FROM folders WHERE id = ?1", [target], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        match target_deleted_at {
+            None => return Err(format!("Target folder '{}' not found", target)),
+            Some(Some(_)) => return Err(format!("Target folder '{}' is in the trash", target)),
+            Some(None) => {}
+        }
+    }
+    
+    // write_note upserts, so an existing id would silently be overwritten
+    let id_taken = tx
+        .query_row("SELECT 1 FROM notes WHERE id = ?1", [&new_id], |_| Ok(()))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if id_taken {
+        return Err(format!("Note '{}' already exists", new_id));
+    }
+    
+    let source = tx
+        .query_row(&format!("SELECT {} FROM notes WHERE id = ?1", NOTE_COLUMNS), [&note_id], note_from_row)
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Note '{}' not found", note_id))?;
+    if source.is_locked {
+        return Err(format!("Note '{}' is locked; unlock it before copying", note_id));
+    }
+    let mut sources = [source];
+    populate_note_tags(&tx, &mut sources)?;
+    let [source] = sources;
+    
+    // A copy is a fresh, active note: no trash state, pin, manual order or daily-note date
+    let now = now_timestamp();
+    let copy = Note {
+        id: new_id,
+        folder_id: target_folder_id,
+        daily_note_date: None,
+        created_at: now.clone(),
+        updated_at: now,
+        deleted_at: None,
+        sort_order: None,
+        is_pinned: false,
+        copied_from: Some(source.id.clone()),
+        ..source
+    };
+    write_note(&tx, &copy, false)?;
+    tx.execute("UPDATE notes SET copied_from = ?1 WHERE id = ?2", (&copy.copied_from, &copy.id))
+        .map_err(|e| e.to_string())?;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("📄 Copied note {} -> {}", note_id, copy.id);
+    Ok(copy)
+}
+
+/// Point every noteLink to `old_id` at `new_id` instead; returns the number of links changed
+fn retarget_note_links(node: &mut serde_json::Value, old_id: &str, new_id: &str) -> usize {
+    let mut changed = 0;
+    if node.get("type").and_then(|t| t.as_str()) == Some("noteLink") {
+        if let Some(attrs) = node.get_mut("attrs").and_then(|a| a.as_object_mut()) {
+            let is_note_link = attrs.get("linkType").and_then(|t| t.as_str()).unwrap_or("note") != "folder";
+            if is_note_link && attrs.get("targetId").and_then(|t| t.as_str()) == Some(old_id) {
+                attrs.insert("targetId".to_string(), serde_json::Value::String(new_id.to_string()));
+                changed += 1;
             }
         }
     }
+    if let Some(children) = node.get_mut("content").and_then(|c| c.as_array_mut()) {
+        for child in children {
+            changed += retarget_note_links(child, old_id, new_id);
+        }
+    }
+    changed
+}
+
+/// Re-key a note from `old_id` to `new_id` (e.g. after an id scheme change), in one transaction
+/// The row is re-inserted under the new id so note_tags can follow it without breaking the FK,
+/// then everything that refers to the old id is moved over: tags, copied_from, the save audit,
+/// folder-delete bookkeeping and noteLinks in other notes' content (notes_fts follows via the
+/// notes triggers). Locked notes can't have their links rewritten. The old id gets a tombstone
+/// so sync peers drop it
+#[tauri::command]
+pub fn change_note_id(old_id: String, new_id: String, state: State<DbConnection>) -> Result<String, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
     
-    // ✅ UPSERT: Use INSERT ... ON CONFLICT instead of INSERT OR REPLACE
-    // This preserves row identity and is safer
-    conn.execute(
-        "INSERT INTO notes 
-        (id, title, description, description_visible, emoji, content, tags_visible, is_favorite, 
-         folder_id, daily_note_date, created_at, updated_at, deleted_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
-        ON CONFLICT(id) DO UPDATE SET
-            title = excluded.title,
-            description = excluded.description,
-            description_visible = excluded.description_visible,
-            emoji = excluded.emoji,
-            content = excluded.content,
-            tags_visible = excluded.tags_visible,
-            is_favorite = excluded.is_favorite,
-            folder_id = excluded.folder_id,
-            daily_note_date = excluded.daily_note_date,
-            updated_at = excluded.updated_at,
-            deleted_at = excluded.deleted_at",
-        (
-            &note.id,
-            &note.title,
-            &note.description,
-            note.description_visible as i32,
-            &note.emoji,
-            &note.content,
-            note.tags_visible as i32,
-            note.is_favorite as i32,
-            &note.folder_id,
-            &note.daily_note_date,
-            &note.created_at,
-            &note.updated_at,
-            &note.deleted_at,
-        ),
+    let new_id = new_id.trim().to_string();
+    validate_note_id(&new_id)?;
+    if new_id == old_id {
+        return Ok(format!("Note '{}' already has that id", old_id));
+    }
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let exists = |id: &str| -> Result<bool, String> {
+        tx.query_row("SELECT 1 FROM notes WHERE id = ?1", [id], |_| Ok(()))
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(|e| e.to_string())
+    };
+    if !exists(&old_id)? {
+        return Err(format!("Note '{}' not found", old_id));
+    }
+    if exists(&new_id)? {
+        return Err(format!("Note '{}' already exists", new_id));
+    }
+    
+    // Copy the row (lock columns included) so the new id exists before any child row points at it
+    tx.execute(
+        "INSERT INTO notes (id, title, description, description_visible, emoji, content, tags_visible,
+                            is_favorite, folder_id, daily_note_date, created_at, updated_at, deleted_at,
+                            sort_order, content_hash, is_pinned, is_locked, locked_content, lock_salt,
+                            lock_nonce, copied_from)
+         SELECT ?2, title, description, description_visible, emoji, content, tags_visible,
+                is_favorite, folder_id, daily_note_date, created_at, updated_at, deleted_at,
+                sort_order, content_hash, is_pinned, is_locked, locked_content, lock_salt,
+                lock_nonce, copied_from
+         FROM notes WHERE id = ?1",
+        (&old_id, &new_id),
     )
     .map_err(|e| e.to_string())?;
     
-    // Ensure all tags exist in tags table (idempotent upsert)
-    // This prevents FK violations when inserting into note_tags
-    for tag in &note.tags {
-        conn.execute(
-            "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at)
-             VALUES (?1, '', 1, 0, NULL, ?2, ?2)
-             ON CONFLICT(name) DO NOTHING",
-            (tag, &note.updated_at),
-        )
-        .map_err(|e| e.to_string())?;
-    }
+    for sql in [
+        "UPDATE note_tags SET note_id = ?2 WHERE note_id = ?1",
+        "UPDATE notes SET copied_from = ?2 WHERE copied_from = ?1",
+        "UPDATE save_audit SET note_id = ?2 WHERE note_id = ?1",
+        "UPDATE folder_delete_cascade SET entity_id = ?2 WHERE entity_type = 'note' AND entity_id = ?1",
+    ] {
+        tx.execute(sql, (&old_id, &new_id)).map_err(|e| e.to_string())?;
+    }
+    
+    // LIKE is only a prefilter; retarget_note_links matches targetId exactly
+    let linking: Vec<(String, String)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT id, content FROM notes
+                 WHERE id != ?1 AND is_locked = 0 AND content LIKE ?2 ESCAPE '\\'",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map((&old_id, format!("%{}%", escape_like(&old_id))), |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        rows
+    };
+    let now = now_timestamp();
+    let mut relinked = 0;
+    for (note_id, content) in linking {
+        let mut doc = match serde_json::from_str::<serde_json::Value>(&content) {
+            Ok(doc) => doc,
+            Err(_) => continue,
+        };
+        if retarget_note_links(&mut doc, &old_id, &new_id) == 0 {
+            continue;
+        }
+        let content = doc.to_string();
+        let audit = begin_save_audit(&tx, &note_id)?;
+        tx.execute(
+            "UPDATE notes SET content = ?1, content_hash = ?2, updated_at = ?3 WHERE id = ?4",
+            (&content, content_hash(&content), &now, &note_id),
+        )
+        .map_err(|e| e.to_string())?;
+        record_save_audit(&tx, audit, "change_note_id", &note_id, &content)?;
+        relinked += 1;
+    }
+    
+    tx.execute("DELETE FROM notes WHERE id = ?1", [&old_id]).map_err(|e| e.to_string())?;
+    write_tombstone(&tx, "note", &old_id)?;
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🔑 Re-keyed note {} -> {} ({} linking notes updated)", old_id, new_id, relinked);
+    Ok(format!("Note '{}' is now '{}'", old_id, new_id))
+}
+
+/// Remove `tag_name` from many notes in one transaction (the tag itself is kept)
+/// Returns the number of notes that lost the tag; their updated_at is bumped
+#[tauri::command]
+pub fn bulk_remove_tag(note_ids: Vec<String>, tag_name: String, state: State<DbConnection>) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let tag_name = normalize_tag_name(&tag_name, lowercase_tags(conn)?);
+    let now = now_timestamp();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let mut changed = 0;
+    {
+        let mut remove_tag = tx
+            .prepare("DELETE FROM note_tags WHERE note_id = ?1 AND tag_name = ?2")
+            .map_err(|e| e.to_string())?;
+        let mut touch = tx
+            .prepare("UPDATE notes SET updated_at = ?2 WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+        for note_id in &note_ids {
+            if remove_tag.execute((note_id, &tag_name)).map_err(|e| e.to_string())? > 0 {
+                touch.execute((note_id, &now)).map_err(|e| e.to_string())?;
+                changed += 1;
+            }
+        }
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("🏷️ Removed tag '{}' from {} notes", tag_name, changed);
+    Ok(changed)
+}
+
+/// Remove every tag from one note or folder (the tags themselves are kept)
+/// `table` / `junction` / `column` are fixed identifiers from the two callers, never user input
+fn clear_entity_tags(
+    conn: &mut Connection,
+    table: &str,
+    junction: &str,
+    column: &str,
+    id: &str,
+) -> Result<usize, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    let exists = tx
+        .query_row(&format!("SELECT 1 FROM {} WHERE id = ?1", table), [id], |_| Ok(()))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .is_some();
+    if !exists {
+        let kind = if table == "folders" { "Folder" } else { "Note" };
+        return Err(format!("{} '{}' not found", kind, id));
+    }
+    
+    let removed = tx
+        .execute(&format!("DELETE FROM {} WHERE {} = ?1", junction, column), [id])
+        .map_err(|e| e.to_string())?;
+    if removed > 0 {
+        tx.execute(&format!("UPDATE {} SET updated_at = ?2 WHERE id = ?1", table), (id, now_timestamp()))
+            .map_err(|e| e.to_string())?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+/// Remove all tags from a note without re-saving it; returns the number removed
+/// updated_at is only bumped when the note actually had tags
+#[tauri::command]
+pub fn clear_note_tags(note_id: String, state: State<DbConnection>) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let removed = clear_entity_tags(conn, "notes", "note_tags", "note_id", &note_id)?;
+    
+    println!("🏷️ Cleared {} tags from note {}", removed, note_id);
+    Ok(removed)
+}
+
+/// Remove all tags from a folder (see clear_note_tags)
+#[tauri::command]
+pub fn clear_folder_tags(folder_id: String, state: State<DbConnection>) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let removed = clear_entity_tags(conn, "folders", "folder_tags", "folder_id", &folder_id)?;
+    
+    println!("🏷️ Cleared {} tags from folder {}", removed, folder_id);
+    Ok(removed)
+}
+
+/// Save many notes in ONE transaction (bulk import)
+/// Same upsert + tag logic as save_note, but without a commit per note
+#[tauri::command]
+pub fn save_notes_batch(
+    notes: Vec<Note>,
+    server_timestamps: Option<bool>,
+    state: State<DbConnection>,
+) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
     
-    // Delete existing tag relationships
-    conn.execute(
-        "DELETE FROM note_tags WHERE note_id = ?1",
-        [&note.id],
-    )
-    .map_err(|e| e.to_string())?;
+    let server_timestamps = server_timestamps.unwrap_or(false);
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
     
-    // Insert new tag relationships
-    for tag in &note.tags {
-        conn.execute(
-            "INSERT INTO note_tags (note_id, tag_name) VALUES (?1, ?2)",
-            (&note.id, tag),
-        )
-        .map_err(|e| e.to_string())?;
+    for note in &notes {
+        let audit = begin_save_audit(&tx, &note.id)?;
+        write_note(&tx, note, server_timestamps)?;
+        record_save_audit(&tx, audit, "save_notes_batch", &note.id, &note.content)?;
     }
     
-    Ok(format!("Note saved: {}", note.id))
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("💾 Batch saved {} notes", notes.len());
+    Ok(notes.len())
 }
 
-/// Load a single note by ID
+/// Counts, age and content size of everything in the trash (e.g. before purging)
 #[tauri::command]
-pub fn load_note(note_id: String, state: State<DbConnection>) -> Result<Note, String> {
+pub fn get_trash_summary(state: State<DbConnection>) -> Result<TrashSummary, String> {
     let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
     
-    // Load note
-    let mut note = conn
+    // CAST AS BLOB so LENGTH counts bytes rather than characters
+    let (note_count, oldest_note, total_content_bytes): (i64, Option<String>, i64) = conn
         .query_row(
-            "SELECT id, title, description, description_visible, emoji, content, tags_visible, 
-             is_favorite, folder_id, daily_note_date, created_at, updated_at, deleted_at 
-             FROM notes WHERE id = ?1",
-            [&note_id],
-            |row| {
-                Ok(Note {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    description: row.get(2)?,
-                    description_visible: row.get::<_, i32>(3)? != 0,
-                    emoji: row.get(4)?,
-                    content: row.get(5)?,
-                    tags: Vec::new(), // Will be populated below
-                    tags_visible: row.get::<_, i32>(6)? != 0,
-                    is_favorite: row.get::<_, i32>(7)? != 0,
-                    folder_id: row.get(8)?,
-                    daily_note_date: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                    deleted_at: row.get(12)?,
-                })
-            },
+            "SELECT COUNT(*), MIN(deleted_at), COALESCE(SUM(LENGTH(CAST(content AS BLOB))), 0)
+             FROM notes WHERE deleted_at IS NOT NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         )
         .map_err(|e| e.to_string())?;
     
-    // Load tags
-    let mut stmt = conn
-        .prepare("SELECT tag_name FROM note_tags WHERE note_id = ?1")
+    let (folder_count, oldest_folder): (i64, Option<String>) = conn
+        .query_row(
+            "SELECT COUNT(*), MIN(deleted_at) FROM folders WHERE deleted_at IS NOT NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
         .map_err(|e| e.to_string())?;
     
-    let tags = stmt
-        .query_map([&note_id], |row| row.get(0))
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<String>>>()
-        .map_err(|e| e.to_string())?;
+    // Canonical timestamps compare correctly as text
+    let oldest_deleted_at = match (oldest_note, oldest_folder) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
     
-    note.tags = tags;
+    Ok(TrashSummary {
+        note_count,
+        folder_count,
+        oldest_deleted_at,
+        total_content_bytes,
+    })
+}
+
+/// Order folders so every parent present in the batch precedes its children (input order otherwise kept)
+/// Parents outside the batch are assumed to exist already. Errors on duplicate ids or a cycle
+fn sort_folders_parents_first(folders: Vec<Folder>) -> Result<Vec<Folder>, String> {
+    let mut index_by_id: HashMap<String, usize> = HashMap::new();
+    for (i, folder) in folders.iter().enumerate() {
+        if index_by_id.insert(folder.id.clone(), i).is_some() {
+            return Err(format!("Folder '{}' appears more than once in the batch", folder.id));
+        }
+    }
     
-    Ok(note)
+    // Kahn's algorithm over in-batch parent -> child edges
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); folders.len()];
+    let mut pending_parent = vec![false; folders.len()];
+    for (i, folder) in folders.iter().enumerate() {
+        if let Some(&parent) = folder.parent_id.as_ref().and_then(|p| index_by_id.get(p)) {
+            children[parent].push(i);
+            pending_parent[i] = true;
+        }
+    }
+    
+    let mut order: Vec<usize> = Vec::with_capacity(folders.len());
+    let mut ready: VecDeque<usize> = (0..folders.len()).filter(|&i| !pending_parent[i]).collect();
+    while let Some(i) = ready.pop_front() {
+        order.push(i);
+        for &child in &children[i] {
+            pending_parent[child] = false;
+            ready.push_back(child);
+        }
+    }
+    
+    if order.len() < folders.len() {
+        let cyclic: Vec<&str> = folders
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| pending_parent[*i])
+            .map(|(_, folder)| folder.id.as_str())
+            .collect();
+        return Err(format!("Folder batch contains a parent_id cycle: {}", cyclic.join(", ")));
+    }
+    
+    let mut slots: Vec<Option<Folder>> = folders.into_iter().map(Some).collect();
+    Ok(order.into_iter().filter_map(|i| slots[i].take()).collect())
+}
+
+/// Save many folders in ONE transaction (hierarchy import)
+/// Parents are written before their children so FK checks pass regardless of input order
+#[tauri::command]
+pub fn save_folders_batch(folders: Vec<Folder>, state: State<DbConnection>) -> Result<usize, String> {
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    let folders = sort_folders_parents_first(folders)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    
+    for folder in &folders {
+        write_folder(&tx, folder)?;
+    }
+    
+    tx.commit().map_err(|e| e.to_string())?;
+    
+    println!("💾 Batch saved {} folders", folders.len());
+    Ok(folders.len())
 }
 
-/// Load all notes
+/// Enforce the trash retention policy (`trash.retention_days` setting) on demand
+/// Also runs automatically in init_database. Returns the number of items purged
 #[tauri::command]
-pub fn load_all_notes(state: State<DbConnection>) -> Result<Vec<Note>, String> {
+pub fn enforce_trash_policy(state: State<DbConnection>) -> Result<usize, String> {
     let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
     
-    // No checkpoint needed! Same connection automatically sees WAL writes
-    // Load all notes (including deleted ones - filtering happens in frontend)
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, title, description, description_visible, emoji, content, tags_visible, 
-             is_favorite, folder_id, daily_note_date, created_at, updated_at, deleted_at 
-             FROM notes 
-             ORDER BY updated_at DESC"
-        )
-        .map_err(|e| e.to_string())?;
+    let purged = purge_expired_trash(conn)?;
     
-    let mut notes: Vec<Note> = stmt
-        .query_map([], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                description: row.get(2)?,
-                description_visible: row.get::<_, i32>(3)? != 0,
-                emoji: row.get(4)?,
-                content: row.get(5)?,
-                tags: Vec::new(), // Will be populated below
-                tags_visible: row.get::<_, i32>(6)? != 0,
-                is_favorite: row.get::<_, i32>(7)? != 0,
-                folder_id: row.get(8)?,
-                daily_note_date: row.get(9)?,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-                deleted_at: row.get(12)?,
-            })
-        })
+    println!("🧹 Trash policy enforced: {} items purged", purged);
+    Ok(purged)
+}
+
+/// Check whether a note id exists without loading the row
+#[tauri::command]
+pub fn note_exists(note_id: String, state: State<DbConnection>) -> Result<bool, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    
+    let exists = conn
+        .query_row("SELECT 1 FROM notes WHERE id = ?1 LIMIT 1", [&note_id], |_| Ok(()))
+        .optional()
         .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<Note>>>()
-        .map_err(|e| e.to_string())?;
+        .is_some();
     
-    // Load ALL tags in ONE query (Apple Notes approach - no N+1!)
-    let mut tag_stmt = conn
-        .prepare("SELECT note_id, tag_name FROM note_tags")
-        .map_err(|e| e.to_string())?;
+    Ok(exists)
+}
+
+/// Batch existence check: maps every requested id to whether it exists
+#[tauri::command]
+pub fn notes_exist(note_ids: Vec<String>, state: State<DbConnection>) -> Result<HashMap<String, bool>, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
     
-    let tag_rows = tag_stmt
-        .query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-        })
+    let mut result: HashMap<String, bool> = note_ids.iter().map(|id| (id.clone(), false)).collect();
+    if note_ids.is_empty() {
+        return Ok(result);
+    }
+    
+    let placeholders = note_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let mut stmt = conn
+        .prepare(&format!("SELECT id FROM notes WHERE id IN ({})", placeholders))
         .map_err(|e| e.to_string())?;
     
-    // Group tags by note_id in memory (fast HashMap lookup)
-    let mut tags_by_note: HashMap<String, Vec<String>> = HashMap::new();
-    for result in tag_rows {
-        let (note_id, tag) = result.map_err(|e| e.to_string())?;
-        tags_by_note.entry(note_id).or_insert_with(Vec::new).push(tag);
-    }
+    let found = stmt
+        .query_map(rusqlite::params_from_iter(note_ids.iter()), |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
     
-    // Assign tags to notes (O(1) lookup per note)
-    for note in &mut notes {
-        note.tags = tags_by_note.remove(&note.id).unwrap_or_default();
+    for id in found {
+        result.insert(id.map_err(|e| e.to_string())?, true);
     }
     
-    Ok(notes)
+    Ok(result)
 }
 
-/// Search notes using FTS5 (full-text search)
-/// Returns ranked results matching the query
+/// Load full notes (with tags) for a set of ids, in the order given
+/// Missing ids are omitted; a repeated id is returned once, at its first position
 #[tauri::command]
-pub fn search_notes(query: String, state: State<DbConnection>) -> Result<Vec<Note>, String> {
+pub fn get_notes_by_ids(note_ids: Vec<String>, state: State<DbConnection>) -> Result<Vec<Note>, String> {
     let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
     
-    // FTS5 ranked search - returns notes ordered by relevance
+    if note_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    let placeholders = note_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
     let mut stmt = conn
-        .prepare(
-            "SELECT notes.id, notes.title, notes.description, notes.description_visible, 
-                    notes.emoji, notes.content, notes.tags_visible, notes.is_favorite, 
-                    notes.folder_id, notes.daily_note_date, notes.created_at, notes.updated_at, 
-                    notes.deleted_at
-             FROM notes
-             JOIN notes_fts ON notes.id = notes_fts.note_id
-             WHERE notes_fts MATCH ?1 AND notes.deleted_at IS NULL
-             ORDER BY rank
-             LIMIT 50"
-        )
+        .prepare(&format!("SELECT {} FROM notes WHERE id IN ({})", NOTE_COLUMNS, placeholders))
         .map_err(|e| e.to_string())?;
     
     let mut notes: Vec<Note> = stmt
-        .query_map([&query], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                description: row.get(2)?,
-                description_visible: row.get::<_, i32>(3)? != 0,
-                emoji: row.get(4)?,
-                content: row.get(5)?,
-                tags: Vec::new(), // Will be populated below
-                tags_visible: row.get::<_, i32>(6)? != 0,
-                is_favorite: row.get::<_, i32>(7)? != 0,
-                folder_id: row.get(8)?,
-                daily_note_date: row.get(9)?,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-                deleted_at: row.get(12)?,
-            })
-        })
+        .query_map(rusqlite::params_from_iter(note_ids.iter()), note_from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<Note>>>()
         .map_err(|e| e.to_string())?;
     
-    // Load tags for search results (batch load)
-    if !notes.is_empty() {
-        let note_ids: Vec<String> = notes.iter().map(|n| n.id.clone()).collect();
-        let placeholders = note_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        let query_str = format!("SELECT note_id, tag_name FROM note_tags WHERE note_id IN ({})", placeholders);
-        
-        let mut tag_stmt = conn.prepare(&query_str).map_err(|e| e.to_string())?;
-        let tag_rows = tag_stmt
-            .query_map(rusqlite::params_from_iter(note_ids.iter()), |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            })
-            .map_err(|e| e.to_string())?;
-        
-        let mut tags_by_note: HashMap<String, Vec<String>> = HashMap::new();
-        for result in tag_rows {
-            let (note_id, tag) = result.map_err(|e| e.to_string())?;
-            tags_by_note.entry(note_id).or_insert_with(Vec::new).push(tag);
-        }
-        
-        for note in &mut notes {
-            note.tags = tags_by_note.remove(&note.id).unwrap_or_default();
-        }
+    let mut position: HashMap<&str, usize> = HashMap::new();
+    for (i, id) in note_ids.iter().enumerate() {
+        position.entry(id.as_str()).or_insert(i);
     }
+    notes.sort_by_key(|note| position.get(note.id.as_str()).copied());
+    
+    populate_note_tags(conn, &mut notes)?;
     
     Ok(notes)
 }
 
-/// Save or update a folder
+/// Group active notes with identical content (same non-empty content_hash)
+/// Each inner vec holds the ids of one duplicate group, most recently updated first
 #[tauri::command]
-pub fn save_folder(folder: Folder, state: State<DbConnection>) -> Result<String, String> {
+pub fn find_duplicate_notes(state: State<DbConnection>) -> Result<Vec<Vec<String>>, String> {
     let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
-    
-    println!(
-        "💾 Saving folder {} | name: {}",
-        &folder.id[..20.min(folder.id.len())],
-        folder.name
-    );
-    
-    // Upsert folder
-    conn.execute(
-        "INSERT INTO folders 
-        (id, name, parent_id, description, description_visible, color, emoji, 
-         tags_visible, is_favorite, is_expanded, created_at, updated_at, deleted_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
-        ON CONFLICT(id) DO UPDATE SET
-            name = excluded.name,
-            parent_id = excluded.parent_id,
-            description = excluded.description,
-            description_visible = excluded.description_visible,
-            color = excluded.color,
-            emoji = excluded.emoji,
-            tags_visible = excluded.tags_visible,
-            is_favorite = excluded.is_favorite,
-            is_expanded = excluded.is_expanded,
-            updated_at = excluded.updated_at,
-            deleted_at = excluded.deleted_at",
-        (
-            &folder.id,
-            &folder.name,
-            &folder.parent_id,
-            &folder.description,
-            folder.description_visible as i32,
-            &folder.color,
-            &folder.emoji,
-            folder.tags_visible as i32,
-            folder.is_favorite as i32,
-            folder.is_expanded as i32,
-            &folder.created_at,
-            &folder.updated_at,
-            &folder.deleted_at,
-        ),
-    )
-    .map_err(|e| e.to_string())?;
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
     
-    // Ensure all tags exist (prevent FK violations)
-    for tag in &folder.tags {
-        conn.execute(
-            "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at)
-             VALUES (?1, '', 1, 0, NULL, ?2, ?2)
-             ON CONFLICT(name) DO NOTHING",
-            (tag, &folder.updated_at),
+    let mut stmt = conn
+        .prepare(
+            "SELECT content_hash, id FROM notes
+             WHERE deleted_at IS NULL AND content_hash IS NOT NULL AND content_hash != ''
+               AND content_hash IN (
+                   SELECT content_hash FROM notes
+                   WHERE deleted_at IS NULL
+                   GROUP BY content_hash HAVING COUNT(*) > 1
+               )
+             ORDER BY content_hash, updated_at DESC"
         )
         .map_err(|e| e.to_string())?;
-    }
     
-    // Delete existing tag relationships
-    conn.execute(
-        "DELETE FROM folder_tags WHERE folder_id = ?1",
-        [&folder.id],
-    )
-    .map_err(|e| e.to_string())?;
-    
-    // Insert new tag relationships
-    for tag in &folder.tags {
-        conn.execute(
-            "INSERT INTO folder_tags (folder_id, tag_name) VALUES (?1, ?2)",
-            (&folder.id, tag),
-        )
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
         .map_err(|e| e.to_string())?;
+    
+    // Rows arrive grouped by hash - split on hash boundaries
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut current_hash: Option<String> = None;
+    for result in rows {
+        let (hash, id) = result.map_err(|e| e.to_string())?;
+        if current_hash.as_deref() != Some(hash.as_str()) {
+            groups.push(Vec::new());
+            current_hash = Some(hash);
+        }
+        if let Some(group) = groups.last_mut() {
+            group.push(id);
+        }
     }
     
-    Ok(format!("Folder saved: {}", folder.id))
+    Ok(groups)
 }
 
-/// Load all folders
+/// One note in a DuplicateGroup
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateMember {
+    pub id: String,
+    pub updated_at: String,
+}
+
+/// Active notes sharing a title (see get_duplicate_titles)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// Title of the most recently updated member
+    pub title: String,
+    pub notes: Vec<DuplicateMember>,
+}
+
+/// Group active notes whose titles match after trimming, ignoring (ASCII) case
+/// Untitled notes are skipped; members are most recently updated first
 #[tauri::command]
-pub fn load_all_folders(state: State<DbConnection>) -> Result<Vec<Folder>, String> {
+pub fn get_duplicate_titles(state: State<DbConnection>) -> Result<Vec<DuplicateGroup>, String> {
     let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
     
-    // Load all folders (including deleted ones - filtering happens in frontend)
     let mut stmt = conn
         .prepare(
-            "SELECT id, name, parent_id, description, description_visible, color, emoji, 
-             tags_visible, is_favorite, is_expanded, created_at, updated_at, deleted_at 
-             FROM folders"
+            "SELECT lower(trim(title)) AS title_key, title, id, updated_at FROM notes
+             WHERE deleted_at IS NULL AND trim(title) != ''
+               AND lower(trim(title)) IN (
+                   SELECT lower(trim(title)) FROM notes
+                   WHERE deleted_at IS NULL
+                   GROUP BY lower(trim(title)) HAVING COUNT(*) > 1
+               )
+             ORDER BY title_key, updated_at DESC"
         )
         .map_err(|e| e.to_string())?;
     
-    let mut folders: Vec<Folder> = stmt
+    let rows = stmt
         .query_map([], |row| {
-            Ok(Folder {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                parent_id: row.get(2)?,
-                description: row.get(3)?,
-                description_visible: row.get::<_, i32>(4)? != 0,
-                color: row.get(5)?,
-                emoji: row.get(6)?,
-                tags: Vec::new(), // Populated below
-                tags_visible: row.get::<_, i32>(7)? != 0,
-                is_favorite: row.get::<_, i32>(8)? != 0,
-                is_expanded: row.get::<_, i32>(9)? != 0,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-                deleted_at: row.get(12)?,
-            })
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                DuplicateMember { id: row.get(2)?, updated_at: row.get(3)? },
+            ))
         })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<Folder>>>()
         .map_err(|e| e.to_string())?;
     
-    // Load all tags in one query (Apple Notes approach - no N+1)
-    let mut tag_stmt = conn
-        .prepare("SELECT folder_id, tag_name FROM folder_tags")
-        .map_err(|e| e.to_string())?;
+    // Rows arrive grouped by normalized title - split on key boundaries
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut current_key: Option<String> = None;
+    for result in rows {
+        let (key, title, member) = result.map_err(|e| e.to_string())?;
+        if current_key.as_deref() != Some(key.as_str()) {
+            groups.push(DuplicateGroup { title: title.trim().to_string(), notes: Vec::new() });
+            current_key = Some(key);
+        }
+        if let Some(group) = groups.last_mut() {
+            group.notes.push(member);
+        }
+    }
     
-    let tag_rows = tag_stmt
-        .query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-        })
-        .map_err(|e| e.to_string())?;
+    Ok(groups)
+}
+
+/// Reparent a folder (targeted update - leaves name/description/color/tags alone)
+/// `new_parent_id = None` moves it to the root
+#[tauri::command]
+pub fn move_folder(folder_id: String, new_parent_id: Option<String>, state: State<DbConnection>) -> Result<String, String> {
+    let conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
     
-    let mut tags_by_folder: HashMap<String, Vec<String>> = HashMap::new();
-    for result in tag_rows {
-        let (folder_id, tag) = result.map_err(|e| e.to_string())?;
-        tags_by_folder.entry(folder_id).or_insert_with(Vec::new).push(tag);
+    if let Some(parent_id) = &new_parent_id {
+        if !ensure_parent_not_trashed(conn, parent_id)? {
+            return Err(DbError::Validation(format!("Target folder '{}' not found", parent_id)).into());
+        }
+        
+        ensure_no_folder_cycle(conn, &folder_id, parent_id)?;
     }
     
-    for folder in &mut folders {
-        folder.tags = tags_by_folder.remove(&folder.id).unwrap_or_default();
+    // Single statement after validation - fast regardless of subtree depth
+    let now = now_timestamp();
+    let updated = conn
+        .execute(
+            "UPDATE folders SET parent_id = ?1, updated_at = ?2 WHERE id = ?3",
+            (&new_parent_id, &now, &folder_id),
+        )
+        .map_err(|e| e.to_string())?;
+    
+    if updated == 0 {
+        return Err(format!("Folder '{}' not found", folder_id));
     }
     
-    Ok(folders)
+    Ok(format!("Folder moved: {}", folder_id))
 }
 
-/// Save or update tag metadata
-#[tauri::command]
-pub fn save_tag(tag: Tag, state: State<DbConnection>) -> Result<String, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+/// At most the first `max` characters of `text` (for log lines; never splits a character or panics)
+fn truncate_chars(text: &str, max: usize) -> &str {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+// Shortest id generate_id (`note-{millis}-{9 chars}`) or the frontend produce
+const MIN_NOTE_ID_LEN: usize = 20;
+
+/// An id a caller picked for a note: long enough for the id formats above, and free of
+/// whitespace / control characters
+fn validate_note_id(id: &str) -> Result<(), DbError> {
+    if id.chars().count() < MIN_NOTE_ID_LEN {
+        return Err(DbError::Validation(format!(
+            "Invalid note id '{}': must be at least {} characters",
+            id, MIN_NOTE_ID_LEN
+        )));
+    }
+    if id.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return Err(DbError::Validation(format!(
+            "Invalid note id '{}': no spaces or control characters allowed",
+            id.escape_debug()
+        )));
+    }
+    Ok(())
+}
+
+// Monotonic counter so ids generated within the same millisecond never collide
+static ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Generate an id in the frontend's format: `{prefix}-{millis}-{9 base36 chars}`
+fn generate_id(prefix: &str) -> String {
+    let now = chrono::Utc::now();
+    let seq = ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut n = (now.timestamp_subsec_nanos() as u64) ^ seq.wrapping_mul(0x9E37_79B9_7F4A_7C15);
     
-    println!("💾 Saving tag metadata: {}", tag.name);
+    const ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let suffix: String = (0..9)
+        .map(|_| {
+            let c = ALPHABET[(n % 36) as usize] as char;
+            n /= 36;
+            c
+        })
+        .collect();
     
-    conn.execute(
-        "INSERT INTO tags (name, description, description_visible, is_favorite, color, created_at, updated_at, deleted_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-         ON CONFLICT(name) DO UPDATE SET
-            description = excluded.description,
-            description_visible = excluded.description_visible,
-            is_favorite = excluded.is_favorite,
-            color = excluded.color,
-            updated_at = excluded.updated_at,
-            deleted_at = excluded.deleted_at",
-        (
-            &tag.name,
-            &tag.description,
-            tag.description_visible as i32,
-            tag.is_favorite as i32,
-            &tag.color,
-            &tag.created_at,
-            &tag.updated_at,
-            &tag.deleted_at,
-        ),
-    )
-    .map_err(|e| e.to_string())?;
+    format!("{}-{}-{}", prefix, now.timestamp_millis(), suffix)
+}
+
+/// Split optional `---` YAML frontmatter from a Markdown body
+fn split_frontmatter(text: &str) -> (Option<serde_yaml::Mapping>, &str) {
+    let rest = match text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) {
+        Some(rest) => rest,
+        None => return (None, text),
+    };
     
-    Ok(format!("Tag saved: {}", tag.name))
+    let (yaml, body) = match rest.find("\n---") {
+        Some(end) => {
+            let after = &rest[end + 4..];
+            let body = after.strip_prefix("\r\n").or_else(|| after.strip_prefix('\n')).unwrap_or(after);
+            (&rest[..end], body)
+        }
+        None => return (None, text),
+    };
+    
+    match serde_yaml::from_str::<serde_yaml::Value>(yaml) {
+        Ok(serde_yaml::Value::Mapping(map)) => (Some(map), body),
+        // Not valid YAML - treat the whole file as content
+        _ => (None, text),
+    }
 }
 
-/// Load all tag metadata
-#[tauri::command]
-pub fn load_all_tags(state: State<DbConnection>) -> Result<Vec<Tag>, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+/// Read the first present string value among `keys` from frontmatter
+fn frontmatter_str(map: &serde_yaml::Mapping, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| match map.get(*key) {
+        Some(serde_yaml::Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Some(serde_yaml::Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+/// Frontmatter tags: a YAML list or a comma/space separated string (leading `#` stripped)
+fn frontmatter_tags(map: &serde_yaml::Mapping) -> Vec<String> {
+    let raw: Vec<String> = match map.get("tags") {
+        Some(serde_yaml::Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(serde_yaml::Value::String(s)) => s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    };
     
-    let mut stmt = conn
-        .prepare(
-            "SELECT name, description, description_visible, is_favorite, color, created_at, updated_at, deleted_at 
-             FROM tags"
-        )
-        .map_err(|e| e.to_string())?;
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw {
+        let tag = tag.trim().trim_start_matches('#').to_string();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Normalize a frontmatter date (RFC3339 or `YYYY-MM-DD`) to RFC3339
+fn parse_import_date(value: &str) -> Option<String> {
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(format_timestamp(dt.with_timezone(&chrono::Utc)));
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| format_timestamp(dt.and_utc()))
+}
+
+/// Convert Markdown text into the editor's TipTap JSON document
+/// Headings (#, ##, ###) become heading blocks; every other non-blank line a paragraph.
+/// Block ids are left null - the editor's BlockIdGenerator fills them on first open
+fn markdown_to_doc(markdown: &str) -> String {
+    let mut blocks: Vec<serde_json::Value> = Vec::new();
     
-    let tags: Vec<Tag> = stmt
-        .query_map([], |row| {
-            Ok(Tag {
-                name: row.get(0)?,
-                description: row.get(1)?,
-                description_visible: row.get::<_, i32>(2)? != 0,
-                is_favorite: row.get::<_, i32>(3)? != 0,
-                color: row.get(4)?,
-                created_at: row.get(5)?,
-                updated_at: row.get(6)?,
-                deleted_at: row.get(7)?,
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        let block = if (1..=3).contains(&hashes) && line[hashes..].starts_with(' ') {
+            serde_json::json!({
+                "type": "heading",
+                "attrs": { "headingLevel": hashes },
+                "content": [{ "type": "text", "text": line[hashes..].trim() }],
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<Tag>>>()
-        .map_err(|e| e.to_string())?;
+        } else {
+            serde_json::json!({
+                "type": "paragraph",
+                "content": [{ "type": "text", "text": line }],
+            })
+        };
+        blocks.push(block);
+    }
     
-    Ok(tags)
+    if blocks.is_empty() {
+        blocks.push(serde_json::json!({ "type": "paragraph" }));
+    }
+    
+    serde_json::json!({ "type": "doc", "content": blocks }).to_string()
 }
 
-/// Delete a tag from the database
-/// Note: Junction tables (note_tags, folder_tags) will cascade delete automatically
-#[tauri::command]
-pub fn delete_tag(tag_name: String, state: State<DbConnection>) -> Result<String, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+/// Build a Note from one Markdown file (frontmatter title/tags/dates, filename fallback)
+fn markdown_file_to_note(path: &Path, text: &str, folder_id: Option<String>) -> Note {
+    let (frontmatter, body) = split_frontmatter(text);
+    let now = now_timestamp();
     
-    // Delete from tags table (junction tables cascade automatically via ON DELETE CASCADE)
-    conn.execute(
-        "DELETE FROM tags WHERE name = ?1",
-        [&tag_name],
-    )
-    .map_err(|e| e.to_string())?;
+    let title = frontmatter
+        .as_ref()
+        .and_then(|map| frontmatter_str(map, &["title"]))
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+    let tags = frontmatter.as_ref().map(frontmatter_tags).unwrap_or_default();
+    let created_at = frontmatter
+        .as_ref()
+        .and_then(|map| frontmatter_str(map, &["created", "created_at", "date"]))
+        .and_then(|v| parse_import_date(&v))
+        .unwrap_or_else(|| now.clone());
+    let updated_at = frontmatter
+        .as_ref()
+        .and_then(|map| frontmatter_str(map, &["updated", "updated_at", "modified"]))
+        .and_then(|v| parse_import_date(&v))
+        .unwrap_or_else(|| created_at.clone());
     
-    Ok(format!("Tag '{}' deleted", tag_name))
+    Note {
+        id: generate_id("note"),
+        title,
+        description: String::new(),
+        description_visible: true,
+        emoji: None,
+        content: markdown_to_doc(body),
+        tags,
+        tags_visible: true,
+        is_favorite: false,
+        folder_id,
+        daily_note_date: None,
+        created_at,
+        updated_at,
+        deleted_at: None,
+        sort_order: None,
+        is_pinned: false,
+        is_locked: false,
+        copied_from: None,
+    }
 }
 
-/// Permanently delete a note from the database
-/// This removes the note record and all associated junction table entries
-#[tauri::command]
-pub fn delete_note_permanently(note_id: String, state: State<DbConnection>) -> Result<String, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+/// Recursively collect folders and notes for a directory (folders parent-first)
+fn scan_markdown_directory(
+    dir: &Path,
+    parent_folder_id: Option<String>,
+    depth: usize,
+    folders: &mut Vec<Folder>,
+    notes: &mut Vec<Note>,
+    skipped: &mut usize,
+) -> Result<(), String> {
+    if depth > MAX_FOLDER_DEPTH {
+        return Err(format!("Directory nesting too deep at '{}'", dir.display()));
+    }
     
-    // Delete from notes table (junction table note_tags will cascade delete automatically)
-    conn.execute(
-        "DELETE FROM notes WHERE id = ?1",
-        [&note_id],
-    )
-    .map_err(|e| e.to_string())?;
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
     
-    println!("🗑️ Permanently deleted note: {}", note_id);
-    Ok(format!("Note '{}' permanently deleted", note_id))
+    for path in entries {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        // Skip hidden entries (.obsidian, .git, .DS_Store, ...)
+        if name.starts_with('.') {
+            continue;
+        }
+        
+        if path.is_dir() {
+            let now = now_timestamp();
+            let folder = Folder {
+                id: generate_id("folder"),
+                name,
+                parent_id: parent_folder_id.clone(),
+                description: String::new(),
+                description_visible: true,
+                color: None,
+                emoji: None,
+                tags: Vec::new(),
+                tags_visible: true,
+                is_favorite: false,
+                is_expanded: false,
+                created_at: now.clone(),
+                updated_at: now,
+                deleted_at: None,
+            };
+            let folder_id = folder.id.clone();
+            folders.push(folder);
+            scan_markdown_directory(&path, Some(folder_id), depth + 1, folders, notes, skipped)?;
+            continue;
+        }
+        
+        let is_markdown = path
+            .extension()
+            .map(|ext| {
+                let ext = ext.to_string_lossy().to_lowercase();
+                ext == "md" || ext == "markdown"
+            })
+            .unwrap_or(false);
+        
+        match (is_markdown, std::fs::read_to_string(&path)) {
+            (true, Ok(text)) => notes.push(markdown_file_to_note(&path, &text, parent_folder_id.clone())),
+            // Non-markdown or non-UTF-8 files are skipped
+            _ => *skipped += 1,
+        }
+    }
+    
+    Ok(())
 }
 
-/// Permanently delete a folder from the database
-/// This removes the folder record and all associated junction table entries
+/// Import a directory of Markdown files (e.g. from another notes app)
+/// Subdirectories become nested folders under `target_folder_id` (or the root);
+/// everything is written in ONE transaction via the batch save path
 #[tauri::command]
-pub fn delete_folder_permanently(folder_id: String, state: State<DbConnection>) -> Result<String, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+pub fn import_markdown_directory(
+    dir: String,
+    target_folder_id: Option<String>,
+    state: State<DbConnection>,
+) -> Result<ImportSummary, String> {
+    let root = Path::new(&dir);
+    if !root.is_dir() {
+        return Err(format!("'{}' is not a directory", dir));
+    }
     
-    // First, update any child folders to remove their parent reference
-    // This prevents foreign key constraint violations
-    conn.execute(
-        "UPDATE folders SET parent_id = NULL WHERE parent_id = ?1",
-        [&folder_id],
-    )
-    .map_err(|e| e.to_string())?;
+    // Scan the filesystem before taking the database lock
+    let mut folders: Vec<Folder> = Vec::new();
+    let mut notes: Vec<Note> = Vec::new();
+    let mut files_skipped = 0;
+    scan_markdown_directory(root, target_folder_id.clone(), 0, &mut folders, &mut notes, &mut files_skipped)?;
     
-    // Then delete the folder (junction table folder_tags will cascade delete automatically)
-    conn.execute(
-        "DELETE FROM folders WHERE id = ?1",
-        [&folder_id],
-    )
-    .map_err(|e| e.to_string())?;
+    let mut conn_guard = state.0.lock().unwrap();
+    let conn = conn_guard.as_mut().ok_or(DbError::NotInitialized)?;
+    ensure_writable(conn)?;
+    
+    // Importing under a trashed folder would land everything straight in the trash
+    if let Some(target) = &target_folder_id {
+        if !ensure_parent_not_trashed(conn, target)? {
+            return Err(format!("Target folder '{}' not found", target));
+        }
+    }
+    
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for folder in &folders {
+        write_folder(&tx, folder)?;
+    }
+    for note in &notes {
+        write_note(&tx, note, false)?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
     
-    println!("🗑️ Permanently deleted folder: {}", folder_id);
-    Ok(format!("Folder '{}' permanently deleted", folder_id))
+    println!(
+        "📥 Imported {} notes and {} folders from {} ({} files skipped)",
+        notes.len(),
+        folders.len(),
+        dir,
+        files_skipped
+    );
+    
+    Ok(ImportSummary {
+        notes_created: notes.len(),
+        folders_created: folders.len(),
+        files_skipped,
+    })
 }
 
-/// Cleanup database on app shutdown (optional but recommended)
-/// Checkpoints WAL to main database to keep files tidy
-#[tauri::command]
-pub fn cleanup_database(state: State<DbConnection>) -> Result<String, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+/// File/directory name for an archive entry: lowercase, alphanumerics kept, runs of anything else
+/// collapsed to `-`, capped at 80 chars; falls back to "untitled"
+fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.trim_end_matches('-').chars().take(80).collect();
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Claim `base` (or `base-2`, `base-3`, ...) within one directory of the archive
+fn unique_name(taken: &mut HashSet<String>, base: &str) -> String {
+    let mut candidate = base.to_string();
+    let mut n = 2;
+    while !taken.insert(candidate.clone()) {
+        candidate = format!("{}-{}", base, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// Plain text of a TipTap node (text nodes concatenated, hard breaks as newlines)
+fn doc_node_text(node: &serde_json::Value) -> String {
+    if let Some(text) = node.get("text").and_then(|t| t.as_str()) {
+        return text.to_string();
+    }
+    if node.get("type").and_then(|t| t.as_str()) == Some("hardBreak") {
+        return "\n".to_string();
+    }
+    node.get("content")
+        .and_then(|c| c.as_array())
+        .map(|children| children.iter().map(doc_node_text).collect())
+        .unwrap_or_default()
+}
+
+/// Convert the editor's TipTap JSON document back into Markdown (inverse of markdown_to_doc)
+/// Headings keep their level; every other block becomes a paragraph of its text.
+/// Content that isn't a TipTap document is exported as-is
+fn doc_to_markdown(content: &str) -> String {
+    let doc: serde_json::Value = match serde_json::from_str(content) {
+        Ok(doc @ serde_json::Value::Object(_)) => doc,
+        _ => return content.to_string(),
+    };
     
-    // Checkpoint WAL to merge pending writes into main database
-    // PASSIVE mode: Non-blocking, best effort
-    conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |_| Ok(())).ok();
+    let blocks = doc.get("content").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+    let paragraphs: Vec<String> = blocks
+        .iter()
+        .map(|block| {
+            let text = doc_node_text(block);
+            let level = block
+                .get("attrs")
+                .and_then(|a| a.get("headingLevel").or_else(|| a.get("level")))
+                .and_then(|l| l.as_u64())
+                .unwrap_or(1)
+                .clamp(1, 6) as usize;
+            if block.get("type").and_then(|t| t.as_str()) == Some("heading") {
+                format!("{} {}", "#".repeat(level), text)
+            } else {
+                text
+            }
+        })
+        .filter(|text| !text.trim().is_empty())
+        .collect();
     
-    Ok("Database cleanup complete".to_string())
+    paragraphs.join("\n\n")
 }
 
-/// Save a single UI state key-value pair
+/// Markdown file for one note: YAML frontmatter (the keys import_markdown_directory reads) + body
+fn note_to_markdown(note: &Note) -> Result<String, String> {
+    let mut frontmatter = serde_yaml::Mapping::new();
+    frontmatter.insert("title".into(), note.title.clone().into());
+    if !note.tags.is_empty() {
+        frontmatter.insert(
+            "tags".into(),
+            serde_yaml::Value::Sequence(note.tags.iter().cloned().map(Into::into).collect()),
+        );
+    }
+    frontmatter.insert("created".into(), note.created_at.clone().into());
+    frontmatter.insert("updated".into(), note.updated_at.clone().into());
+    
+    let yaml = serde_yaml::to_string(&frontmatter).map_err(|e| e.to_string())?;
+    Ok(format!("---\n{}---\n\n{}\n", yaml, doc_to_markdown(&note.content)))
+}
+
+/// Export a folder and its subtree as a zip of Markdown files mirroring the folder hierarchy,
+/// plus a manifest.json (ids, names and archive paths). Trashed items are left out.
+/// Data is read under the lock; the archive is written after releasing it
 #[tauri::command]
-pub fn save_ui_state(key: String, value: String, state: State<DbConnection>) -> Result<String, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+pub fn export_folder_to_zip(folder_id: String, dest_path: String, state: State<DbConnection>) -> Result<ZipExport, String> {
+    let (folders, notes) = {
+        let conn_guard = state.0.lock().unwrap();
+        let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+        
+        // Root first, then by depth so every parent is placed before its children
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} SELECT {} FROM folders
+                 WHERE id IN (SELECT id FROM subtree) AND (deleted_at IS NULL OR id = ?1)
+                 ORDER BY (SELECT MIN(depth) FROM subtree WHERE subtree.id = folders.id), name COLLATE NOCASE",
+                folder_subtree_cte(),
+                FOLDER_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let folders: Vec<Folder> = stmt
+            .query_map([&folder_id], folder_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<Folder>>>()
+            .map_err(|e| e.to_string())?;
+        if folders.is_empty() {
+            return Err(format!("Folder '{}' not found", folder_id));
+        }
+        
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} SELECT {} FROM notes
+                 WHERE notes.folder_id IN (SELECT id FROM subtree) AND notes.deleted_at IS NULL
+                 ORDER BY notes.title COLLATE NOCASE, notes.created_at",
+                folder_subtree_cte(),
+                NOTE_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let mut notes: Vec<Note> = stmt
+            .query_map([&folder_id], note_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<Note>>>()
+            .map_err(|e| e.to_string())?;
+        populate_note_tags(conn, &mut notes)?;
+        
+        (folders, notes)
+    };
     
-    let now = chrono::Utc::now().to_rfc3339();
+    prepare_db_path(&dest_path)?;
     
-    conn.execute(
-        "INSERT INTO settings (key, value, updated_at)
-         VALUES (?1, ?2, ?3)
-         ON CONFLICT(key) DO UPDATE SET
-            value = excluded.value,
-            updated_at = excluded.updated_at",
-        (&key, &value, &now),
+    // Archive directory for each folder; names are unique among siblings
+    let mut dir_by_folder: HashMap<String, String> = HashMap::new();
+    let mut taken_by_dir: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut manifest_folders = Vec::new();
+    for folder in &folders {
+        let parent_dir = folder
+            .parent_id
+            .as_ref()
+            .filter(|_| folder.id != folder_id)
+            .and_then(|parent| dir_by_folder.get(parent))
+            .cloned()
+            .unwrap_or_default();
+        // A folder whose parent was trashed (and so skipped) isn't reachable - leave it out too
+        if folder.id != folder_id && parent_dir.is_empty() {
+            continue;
+        }
+        let name = unique_name(taken_by_dir.entry(parent_dir.clone()).or_default(), &slugify(&folder.name));
+        let dir = format!("{}{}/", parent_dir, name);
+        manifest_folders.push(serde_json::json!({
+            "id": folder.id,
+            "name": folder.name,
+            "parentId": folder.parent_id,
+            "path": dir,
+        }));
+        dir_by_folder.insert(folder.id.clone(), dir);
+    }
+    
+    let tmp_path = format!("{}.tmp", dest_path);
+    let file = std::fs::File::create(&tmp_path).map_err(|source| DbError::Io { path: tmp_path.clone(), source })?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    
+    let mut manifest_notes = Vec::new();
+    for note in &notes {
+        let dir = match note.folder_id.as_ref().and_then(|id| dir_by_folder.get(id)) {
+            Some(dir) => dir,
+            None => continue,
+        };
+        let name = unique_name(taken_by_dir.entry(dir.clone()).or_default(), &slugify(&note.title));
+        let path = format!("{}{}.md", dir, name);
+        
+        zip.start_file(path.as_str(), options).map_err(|e| e.to_string())?;
+        std::io::Write::write_all(&mut zip, note_to_markdown(note)?.as_bytes()).map_err(|e| e.to_string())?;
+        
+        manifest_notes.push(serde_json::json!({
+            "id": note.id,
+            "title": note.title,
+            "folderId": note.folder_id,
+            "tags": note.tags,
+            "locked": note.is_locked,
+            "path": path,
+        }));
+    }
+    
+    let manifest = serde_json::json!({
+        "version": 1,
+        "exportedAt": now_timestamp(),
+        "rootFolderId": folder_id,
+        "folders": manifest_folders,
+        "notes": manifest_notes,
+    });
+    zip.start_file("manifest.json", options).map_err(|e| e.to_string())?;
+    std::io::Write::write_all(
+        &mut zip,
+        serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?.as_bytes(),
     )
     .map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
     
-    Ok(format!("UI state saved: {}", key))
+    // Rename into place so a failed export never leaves a truncated archive at dest_path
+    std::fs::rename(&tmp_path, &dest_path).map_err(|source| DbError::Io { path: dest_path.clone(), source })?;
+    
+    println!("📦 Exported {} notes to {}", manifest_notes.len(), dest_path);
+    Ok(ZipExport { path: dest_path, file_count: manifest_notes.len() })
 }
 
-/// Load a single UI state value by key
+/// Export every active note's metadata as CSV (one row per note) for spreadsheet analysis
+/// Columns: id,title,folder,tags,created_at,updated_at,char_count,is_favorite. `folder` is the
+/// full path ("Work / Projects"), tags are joined with ';', and content is left out - only its
+/// length in characters (the placeholder's, for a locked note). Returns the number of rows written
 #[tauri::command]
-pub fn load_ui_state(key: String, state: State<DbConnection>) -> Result<Option<String>, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+pub fn export_notes_csv(path: String, state: State<DbConnection>) -> Result<usize, String> {
+    let records: Vec<[String; 8]> = {
+        let conn_guard = state.0.lock().unwrap();
+        let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+        
+        let mut stmt = conn
+            .prepare("SELECT id, name, parent_id FROM folders")
+            .map_err(|e| e.to_string())?;
+        let folders: HashMap<String, (String, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_>>()
+            .map_err(|e| e.to_string())?;
+        
+        // Same depth cap as get_folder_path, so a corrupted parent chain can't loop forever
+        let folder_path = |folder_id: String| -> String {
+            let mut names = Vec::new();
+            let mut current = Some(folder_id);
+            while let Some(id) = current {
+                match folders.get(&id) {
+                    Some((name, parent_id)) if names.len() < MAX_FOLDER_DEPTH => {
+                        names.push(name.as_str());
+                        current = parent_id.clone();
+                    }
+                    _ => break,
+                }
+            }
+            names.reverse();
+            names.join(" / ")
+        };
+        
+        let mut tags_by_note = load_tags_by_note(conn, None)?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, title, folder_id, created_at, updated_at, length(content), is_favorite
+                 FROM notes WHERE deleted_at IS NULL ORDER BY created_at, id",
+            )
+            .map_err(|e| e.to_string())?;
+        let records = stmt
+            .query_map([], |row| {
+                let id: String = row.get(0)?;
+                let tags = tags_by_note.remove(&id).unwrap_or_default().join(";");
+                Ok([
+                    id,
+                    row.get(1)?,
+                    row.get::<_, Option<String>>(2)?.map(&folder_path).unwrap_or_default(),
+                    tags,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get::<_, i64>(5)?.to_string(),
+                    row.get::<_, bool>(6)?.to_string(),
+                ])
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<_>>()
+            .map_err(|e| e.to_string())?;
+        records
+    };
     
-    let value = conn
-        .query_row(
-            "SELECT value FROM settings WHERE key = ?1",
-            [&key],
-            |row| row.get(0),
-        )
-        .optional()
+    prepare_db_path(&path)?;
+    
+    let tmp_path = format!("{}.tmp", path);
+    let mut writer = csv::Writer::from_path(&tmp_path).map_err(|e| e.to_string())?;
+    writer
+        .write_record(["id", "title", "folder", "tags", "created_at", "updated_at", "char_count", "is_favorite"])
         .map_err(|e| e.to_string())?;
+    for record in &records {
+        writer.write_record(record).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|source| DbError::Io { path: tmp_path.clone(), source })?;
+    drop(writer);
     
-    Ok(value)
+    // Rename into place so a failed export never leaves a truncated file at `path`
+    std::fs::rename(&tmp_path, &path).map_err(|source| DbError::Io { path: path.clone(), source })?;
+    
+    println!("📊 Exported {} notes to {}", records.len(), path);
+    Ok(records.len())
 }
 
-/// Load all UI state settings (keys starting with 'ui.')
-#[tauri::command]
-pub fn load_all_ui_state(state: State<DbConnection>) -> Result<HashMap<String, String>, String> {
-    let conn_guard = state.0.lock().unwrap();
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
     
-    let mut stmt = conn
-        .prepare("SELECT key, value FROM settings WHERE key LIKE 'ui.%'")
-        .map_err(|e| e.to_string())?;
+    /// A fully set-up schema (same path as init_database) on an in-memory database
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        configure_database(&conn, &InitOptions::default()).unwrap();
+        conn
+    }
     
-    let rows = stmt
-        .query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    fn test_note(id: &str, title: &str, content: &str) -> Note {
+        Note {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: String::new(),
+            description_visible: true,
+            emoji: None,
+            content: content.to_string(),
+            tags: Vec::new(),
+            tags_visible: true,
+            is_favorite: false,
+            folder_id: None,
+            daily_note_date: None,
+            created_at: "2024-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2024-01-01T00:00:00.000Z".to_string(),
+            deleted_at: None,
+            sort_order: None,
+            is_pinned: false,
+            is_locked: false,
+            copied_from: None,
+        }
+    }
+    
+    fn stored_updated_at(conn: &Connection, id: &str) -> String {
+        conn.query_row("SELECT updated_at FROM notes WHERE id = ?1", [id], |row| row.get(0))
+            .unwrap()
+    }
+    
+    #[test]
+    fn server_timestamps_never_regress_updated_at() {
+        let conn = test_db();
+        let mut note = test_note("note-server-timestamps", "Title", "body");
+        
+        write_note(&conn, &note, true).unwrap();
+        let first = stored_updated_at(&conn, &note.id);
+        assert!(first > note.updated_at, "server time replaces the caller's updated_at");
+        
+        // A client clock far ahead wins a plain save...
+        note.updated_at = "2999-01-01T00:00:00.000Z".to_string();
+        write_note(&conn, &note, false).unwrap();
+        // ...and a later server-timestamped save must not move it backwards
+        write_note(&conn, &note, true).unwrap();
+        assert_eq!(stored_updated_at(&conn, &note.id), "2999-01-01T00:00:00.000Z");
+    }
+    
+    fn note_tag_names(conn: &Connection, id: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT tag_name FROM note_tags WHERE note_id = ?1 ORDER BY tag_name")
+            .unwrap();
+        stmt.query_map([id], |row| row.get(0)).unwrap().map(|tag| tag.unwrap()).collect()
+    }
+    
+    #[test]
+    fn normalize_tags_trims_and_collapses_duplicates() {
+        let conn = test_db();
+        let tags: Vec<String> = [" Work ", "Work", "", "  ", "home"].iter().map(|t| t.to_string()).collect();
+        assert_eq!(normalize_tags(&conn, &tags).unwrap(), ["Work", "home"]);
+        
+        put_setting(&conn, "tags.lowercase", "true").unwrap();
+        assert_eq!(normalize_tags(&conn, &tags).unwrap(), ["work", "home"]);
+    }
+    
+    #[test]
+    fn merge_tag_variants_folds_into_oldest_spelling() {
+        let mut conn = test_db();
+        // Variants saved before normalization existed, oldest first
+        conn.execute_batch(
+            "INSERT INTO tags (name, description, description_visible, is_favorite, created_at, updated_at) VALUES
+                ('Work', 'oldest', 1, 0, '2024-01-01T00:00:00.000Z', '2024-01-01T00:00:00.000Z'),
+                ('work', '', 1, 0, '2024-02-01T00:00:00.000Z', '2024-02-01T00:00:00.000Z'),
+                (' work ', '', 1, 0, '2024-03-01T00:00:00.000Z', '2024-03-01T00:00:00.000Z'),
+                ('home', '', 1, 0, '2024-01-01T00:00:00.000Z', '2024-01-01T00:00:00.000Z')",
+        )
+        .unwrap();
+        write_note(&conn, &test_note("note-a", "A", ""), false).unwrap();
+        write_note(&conn, &test_note("note-b", "B", ""), false).unwrap();
+        conn.execute_batch(
+            "INSERT INTO note_tags (note_id, tag_name) VALUES
+                ('note-a', 'Work'), ('note-a', 'work'), ('note-b', ' work '), ('note-b', 'home')",
+        )
+        .unwrap();
+        
+        assert_eq!(merge_tag_variants(&mut conn).unwrap(), 2);
+        
+        let names: Vec<String> = conn
+            .prepare("SELECT name FROM tags ORDER BY name")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|name| name.unwrap())
+            .collect();
+        assert_eq!(names, ["Work", "home"]);
+        assert_eq!(note_tag_names(&conn, "note-a"), ["Work"]);
+        assert_eq!(note_tag_names(&conn, "note-b"), ["Work", "home"]);
+        let description: String = conn
+            .query_row("SELECT description FROM tags WHERE name = 'Work'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(description, "oldest");
+        
+        // Already-canonical tags are left alone on a second run
+        assert_eq!(merge_tag_variants(&mut conn).unwrap(), 0);
+    }
+    
+    #[test]
+    fn normalize_emoji_accepts_single_emoji_clusters() {
+        // Plain, flag, ZWJ family, skin tone, keycap, variation selector
+        for emoji in ["😀", "🇯🇵", "👨‍👩‍👧‍👦", "👍🏽", "1️⃣", "❤️", " 📝 "] {
+            assert_eq!(
+                normalize_emoji(Some(emoji)).unwrap().as_deref(),
+                Some(emoji.trim()),
+                "{:?} should be accepted",
+                emoji
+            );
+        }
+        assert_eq!(normalize_emoji(None).unwrap(), None);
+        assert_eq!(normalize_emoji(Some("")).unwrap(), None);
+    }
+    
+    #[test]
+    fn normalize_emoji_rejects_text_and_multiple_emoji() {
+        for emoji in ["a", "abc", "1", "#", "😀😀", "😀 x"] {
+            assert!(normalize_emoji(Some(emoji)).is_err(), "{:?} should be rejected", emoji);
+        }
+    }
+    
+    #[test]
+    fn normalize_timestamp_canonicalizes_rfc3339() {
+        for input in [
+            "2024-03-01T12:30:00Z",
+            "2024-03-01T12:30:00.000Z",
+            "2024-03-01T12:30:00+00:00",
+            "2024-03-01T14:30:00+02:00",
+            " 2024-03-01T12:30:00Z ",
+        ] {
+            assert_eq!(normalize_timestamp("updatedAt", input).unwrap(), "2024-03-01T12:30:00.000Z", "{:?}", input);
+        }
+    }
+    
+    #[test]
+    fn normalize_timestamp_rejects_bad_formats() {
+        for input in [
+            "",
+            "yesterday",
+            "2024-03-01",
+            "2024-03-01 12:30:00",
+            "2024-13-01T00:00:00Z",
+            "2024-03-01T12:30:00",
+            "1709296200000",
+        ] {
+            assert!(
+                matches!(normalize_timestamp("updatedAt", input), Err(DbError::Validation(_))),
+                "{:?} should be rejected",
+                input
+            );
+        }
+        
+        // The same check guards every save
+        let conn = test_db();
+        let mut note = test_note("note-bad-timestamp", "Title", "");
+        note.created_at = "not a date".to_string();
+        assert!(write_note(&conn, &note, false).is_err());
+        let saved: i64 = conn.query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0)).unwrap();
+        assert_eq!(saved, 0);
+    }
+    
+    /// Ids search_match_query matches for `query`, in no particular order
+    fn search_ids(conn: &Connection, query: &str, substring: bool) -> Result<Vec<String>, String> {
+        let (matches, params) = search_match_query(conn, query, substring, (SEARCH_TITLE_WEIGHT, SEARCH_CONTENT_WEIGHT))?;
+        let mut stmt = conn.prepare(&format!("SELECT id FROM ({}) ORDER BY id", matches)).unwrap();
+        let ids = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| row.get(0))
+            .unwrap()
+            .map(|id| id.unwrap())
+            .collect();
+        Ok(ids)
+    }
+    
+    #[test]
+    fn trigram_index_matches_inside_words() {
+        let conn = test_db();
+        write_note(&conn, &test_note("note-integrated", "Notes", "an integrated workflow"), false).unwrap();
+        write_note(&conn, &test_note("note-other", "Other", "nothing to see"), false).unwrap();
+        
+        // Off by default: substring search is refused rather than silently empty
+        assert!(search_ids(&conn, "grate", true).is_err());
+        
+        set_substring_index(&conn, true).unwrap();
+        assert_eq!(search_ids(&conn, "grate", true).unwrap(), ["note-integrated"]);
+        assert!(search_ids(&conn, "grate", false).unwrap().is_empty(), "word search needs whole tokens");
+        
+        // Notes written after enabling are indexed by the trigram triggers
+        write_note(&conn, &test_note("note-later", "Later", "migrated data"), false).unwrap();
+        assert_eq!(search_ids(&conn, "grate", true).unwrap(), ["note-integrated", "note-later"]);
+    }
+    
+    #[test]
+    fn title_hit_ranks_above_body_hit() {
+        let conn = test_db();
+        // The body-only note mentions the term more often, and is the more recent edit
+        let mut body = test_note("note-body", "Weekly notes", "zebra zebra zebra sightings");
+        body.updated_at = "2024-06-01T00:00:00.000Z".to_string();
+        write_note(&conn, &body, false).unwrap();
+        write_note(&conn, &test_note("note-title", "Zebra", "unrelated text"), false).unwrap();
+        
+        let (matches, params) = search_match_query(&conn, "zebra", false, (SEARCH_TITLE_WEIGHT, SEARCH_CONTENT_WEIGHT)).unwrap();
+        let mut stmt = conn
+            .prepare(&format!("SELECT id FROM ({}) ORDER BY {}", matches, SEARCH_RESULT_ORDER))
+            .unwrap();
+        let ranked: Vec<String> = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| row.get(0))
+            .unwrap()
+            .map(|id| id.unwrap())
+            .collect();
+        assert_eq!(ranked, ["note-title", "note-body"]);
+    }
+    
+    #[test]
+    fn daily_notes_are_not_orphans() {
+        let conn = test_db();
+        let mut daily = test_note("note-daily", "2024-03-01", "");
+        daily.folder_id = Some(DAILY_NOTES_FOLDER_ID.to_string());
+        daily.daily_note_date = Some("2024-03-01".to_string());
+        write_note(&conn, &daily, false).unwrap();
+        let mut orphan = test_note("note-orphan", "Lost", "");
+        orphan.folder_id = Some("folder-deleted".to_string());
+        write_note(&conn, &orphan, false).unwrap();
+        
+        let orphans = load_notes_where(&conn, ORPHANED_NOTES_CLAUSE).unwrap();
+        assert_eq!(orphans.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), ["note-orphan"]);
+    }
+    
+    #[test]
+    fn note_ids_are_validated_and_logged_safely() {
+        assert!(validate_note_id(&generate_id("note")).is_ok());
+        assert!(validate_note_id("note-1700000000000-abc123xyz").is_ok());
+        for bad in ["", "short", "note-1700000000000 abc", "note-1700000000000-\u{7}bc"] {
+            assert!(validate_note_id(bad).is_err(), "{:?} should be rejected", bad);
+        }
+        
+        assert_eq!(truncate_chars("short", 20), "short");
+        assert_eq!(truncate_chars("日本語のタイトルです", 3), "日本語");
+    }
+    
+    #[test]
+    fn legacy_timestamps_are_canonicalized_for_cursors() {
+        let conn = test_db();
+        let note = test_note("note-legacy-timestamp", "Title", "body");
+        write_note(&conn, &note, false).unwrap();
+        conn.execute("UPDATE notes SET updated_at = '2024-03-01T14:30:00+02:00' WHERE id = ?1", [&note.id])
+            .unwrap();
+        
+        backfill_canonical_timestamps(&conn).unwrap();
+        assert_eq!(stored_updated_at(&conn, &note.id), "2024-03-01T12:30:00.000Z");
+        
+        let since = normalize_timestamp("since", "2024-03-01T12:00:00+00:00").unwrap();
+        let ids: Vec<String> = conn
+            .prepare(&format!("SELECT id FROM notes WHERE {}", MODIFIED_SINCE_CLAUSE))
+            .unwrap()
+            .query_map([&since], |row| row.get(0))
+            .unwrap()
+            .map(|id| id.unwrap())
+            .collect();
+        assert_eq!(ids, [note.id]);
+        assert!(normalize_timestamp("since", "yesterday").is_err());
+    }
+    
+    fn save_audit_count(conn: &Connection, id: &str) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM save_audit WHERE note_id = ?1", [id], |row| row.get(0))
+            .unwrap()
+    }
+    
+    #[test]
+    fn save_audit_only_records_writes_that_landed() {
+        let conn = test_db();
+        put_setting(&conn, "debug.audit_saves", "true").unwrap();
+        let mut note = test_note("note-save-audit-000000", "Title", "first");
+        
+        let audit = begin_save_audit(&conn, &note.id).unwrap();
+        write_note(&conn, &note, false).unwrap();
+        record_save_audit(&conn, audit, "save_note", &note.id, &note.content).unwrap();
+        assert_eq!(save_audit_count(&conn, &note.id), 1);
+        
+        // A locked note keeps its stored content, so the save isn't logged
+        conn.execute("UPDATE notes SET is_locked = 1 WHERE id = ?1", [&note.id]).unwrap();
+        note.content = "second".to_string();
+        let audit = begin_save_audit(&conn, &note.id).unwrap();
+        write_note(&conn, &note, false).unwrap();
+        record_save_audit(&conn, audit, "save_note", &note.id, &note.content).unwrap();
+        assert_eq!(save_audit_count(&conn, &note.id), 1);
+        
+        let (old_length, new_length): (Option<i64>, i64) = conn
+            .query_row("SELECT old_length, new_length FROM save_audit WHERE note_id = ?1", [&note.id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((old_length, new_length), (None, 5));
+    }
+    
+    fn test_folder(id: &str, parent_id: Option<&str>) -> Folder {
+        Folder {
+            id: id.to_string(),
+            name: id.to_string(),
+            parent_id: parent_id.map(str::to_string),
+            description: String::new(),
+            description_visible: true,
+            color: None,
+            emoji: None,
+            tags: Vec::new(),
+            tags_visible: true,
+            is_favorite: false,
+            is_expanded: false,
+            created_at: "2024-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2024-01-01T00:00:00.000Z".to_string(),
+            deleted_at: None,
+        }
+    }
+    
+    fn note_placement(conn: &Connection, id: &str) -> (Option<String>, bool) {
+        conn.query_row("SELECT folder_id, deleted_at IS NOT NULL FROM notes WHERE id = ?1", [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
         })
-        .map_err(|e| e.to_string())?;
+        .unwrap()
+    }
     
-    let mut settings = HashMap::new();
-    for result in rows {
-        let (key, value) = result.map_err(|e| e.to_string())?;
-        settings.insert(key, value);
+    #[test]
+    fn remove_folder_applies_each_orphan_policy() {
+        for (policy, active_after, trashed_count) in [
+            ("reparent_to_root", (None, false), 0),
+            ("reparent_to_parent", (Some("parent".to_string()), false), 0),
+            ("delete", (None, true), 1),
+        ] {
+            let mut conn = test_db();
+            write_folder(&conn, &test_folder("parent", None)).unwrap();
+            write_folder(&conn, &test_folder("child", Some("parent"))).unwrap();
+            let mut active = test_note("note-orphan-active-0000", "Active", "body");
+            active.folder_id = Some("child".to_string());
+            write_note(&conn, &active, false).unwrap();
+            let mut trashed = test_note("note-orphan-trashed-000", "Trashed", "body");
+            trashed.folder_id = Some("child".to_string());
+            trashed.deleted_at = Some("2024-01-01T00:00:00.000Z".to_string());
+            write_note(&conn, &trashed, false).unwrap();
+            
+            let orphaned = remove_folder(&mut conn, "child", policy).unwrap();
+            assert_eq!((orphaned.reparented, orphaned.trashed), (1 - trashed_count, trashed_count), "{}", policy);
+            assert_eq!(note_placement(&conn, &active.id), active_after, "{}", policy);
+            assert_eq!(note_placement(&conn, &trashed.id), (None, true), "{}", policy);
+        }
     }
     
-    Ok(settings)
+    #[test]
+    fn range_bounds_cover_whole_days_and_offsets() {
+        assert_eq!(normalize_range_bound("start", "2024-03-07", false).unwrap(), "2024-03-07T00:00:00.000Z");
+        assert_eq!(normalize_range_bound("end", "2024-03-07", true).unwrap(), "2024-03-07T23:59:59.999Z");
+        assert_eq!(
+            normalize_range_bound("end", "2024-03-07T02:00:00+02:00", true).unwrap(),
+            "2024-03-07T00:00:00.000Z"
+        );
+        assert!(normalize_range_bound("start", "last week", false).is_err());
+    }
+    
+    #[test]
+    fn write_folder_rejects_missing_parent_without_foreign_keys() {
+        let conn = test_db();
+        conn.execute("PRAGMA foreign_keys = OFF", []).unwrap();
+        
+        let error = write_folder(&conn, &test_folder("orphan", Some("missing"))).unwrap_err();
+        assert!(error.contains("FOREIGN KEY constraint failed"), "{}", error);
+        
+        let mut trashed = test_folder("trashed-orphan", Some("missing"));
+        trashed.deleted_at = Some("2024-01-01T00:00:00.000Z".to_string());
+        assert!(write_folder(&conn, &trashed).is_err());
+        
+        write_folder(&conn, &test_folder("parent", None)).unwrap();
+        write_folder(&conn, &test_folder("child", Some("parent"))).unwrap();
+    }
 }
-
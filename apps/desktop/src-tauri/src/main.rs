@@ -2,14 +2,22 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod database;
+mod metrics;
+mod watcher;
 
-use database::DbConnection;
+use database::{DbConnection, DeleteHistory};
+use metrics::CommandMetrics;
+use watcher::DbWatcher;
 use std::sync::Mutex;
 
 fn main() {
     tauri::Builder::default()
         .manage(DbConnection(Mutex::new(None)))
-        .invoke_handler(tauri::generate_handler![
+        .manage(DeleteHistory::default())
+        .manage(DbWatcher(Mutex::new(None)))
+        .manage(CommandMetrics::default())
+        // metrics::timed records each command's duration while the metrics.enabled setting is on
+        .invoke_handler(metrics::timed(tauri::generate_handler![
             database::init_database,
             database::save_note,
             database::load_note,
@@ -26,7 +34,109 @@ fn main() {
             database::save_ui_state,
             database::load_ui_state,
             database::load_all_ui_state,
-        ])
+            database::get_folder_path,
+            database::get_database_stats,
+            database::load_notes_metadata,
+            database::delete_folder,
+            database::restore_folder,
+            database::reorder_note,
+            database::change_passphrase,
+            database::get_orphaned_notes,
+            database::reassign_orphaned_notes,
+            database::save_notes_batch,
+            database::enforce_trash_policy,
+            database::note_exists,
+            database::notes_exist,
+            database::find_duplicate_notes,
+            database::move_folder,
+            database::set_note_pinned,
+            database::import_markdown_directory,
+            database::save_ui_state_bool,
+            database::load_ui_state_bool,
+            database::save_ui_state_int,
+            database::load_ui_state_int,
+            database::save_ui_state_json,
+            database::load_ui_state_json,
+            database::get_untagged_notes,
+            database::get_notes_without_folder,
+            database::get_related_notes,
+            database::close_database,
+            database::get_favorites,
+            database::get_tag_suggestions,
+            database::get_note_neighbors,
+            database::cleanup_orphaned_relationships,
+            database::set_folder_expanded,
+            database::get_note_content_length,
+            database::load_note_content_chunk,
+            database::merge_duplicate_tags,
+            database::touch_note,
+            database::backup_database,
+            database::get_notes_modified_since,
+            database::get_folders_modified_since,
+            database::get_tags_modified_since,
+            database::get_tombstones_since,
+            database::get_all_daily_notes,
+            database::ensure_daily_note,
+            database::get_child_folders,
+            database::get_tag_cooccurrence,
+            database::get_notes_in_date_range,
+            database::count_search_results,
+            database::lock_note,
+            database::unlock_note,
+            database::get_folder_note_ids_recursive,
+            database::bulk_move_notes,
+            database::bulk_add_tag,
+            database::bulk_remove_tag,
+            database::get_note_tags,
+            database::get_folder_tags,
+            database::update_note_content,
+            database::search_within_note,
+            database::checkpoint_now,
+            database::get_trash_summary,
+            database::copy_note_to_folder,
+            database::load_settings_by_prefix,
+            database::delete_setting,
+            database::delete_settings_by_prefix,
+            database::get_notes_by_ids,
+            database::probe_database,
+            database::clear_trash,
+            database::get_note_folder,
+            database::save_folders_batch,
+            database::open_daily_note,
+            database::get_tags_for_notes,
+            database::export_folder_to_zip,
+            database::save_template,
+            database::load_all_templates,
+            database::delete_template,
+            database::create_note_from_template,
+            database::get_stale_notes,
+            database::replace_in_note,
+            database::rewrite_inline_tag,
+            database::load_note_previews,
+            database::search_notes_structured,
+            database::reindex_database,
+            database::get_activity_counts,
+            database::get_save_audit,
+            database::is_database_healthy,
+            database::get_duplicate_titles,
+            database::place_note,
+            database::get_folder_size,
+            watcher::start_watching,
+            watcher::stop_watching,
+            database::get_broken_links,
+            metrics::get_command_metrics,
+            metrics::reset_command_metrics,
+            database::change_note_id,
+            database::load_folder_notes_after,
+            database::get_recent_notes,
+            database::clear_note_tags,
+            database::clear_folder_tags,
+            database::get_schema_version,
+            database::export_notes_csv,
+            database::get_used_colors,
+            database::undo_last_delete,
+            database::remove_note_lock,
+        ]))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
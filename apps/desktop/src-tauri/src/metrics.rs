@@ -0,0 +1,124 @@
+use crate::database::DbConnection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Invoke, Manager, Runtime, State};
+
+/// Running totals for one command name
+#[derive(Debug, Default, Clone, Copy)]
+struct Timing {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+/// In-memory per-command timings for this session (see timed)
+/// `enabled` caches the `metrics.enabled` setting so the wrapper never locks the database just to check it
+#[derive(Default)]
+pub struct CommandMetrics {
+    enabled: AtomicBool,
+    timings: Mutex<HashMap<String, Timing>>,
+}
+
+impl CommandMetrics {
+    fn record(&self, command: &str, elapsed: Duration) {
+        let mut timings = self.timings.lock().unwrap();
+        let timing = timings.entry(command.to_string()).or_default();
+        timing.min = if timing.count == 0 { elapsed } else { timing.min.min(elapsed) };
+        timing.max = timing.max.max(elapsed);
+        timing.total += elapsed;
+        timing.count += 1;
+    }
+}
+
+/// One row of get_command_metrics (durations in milliseconds)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandMetric {
+    pub command: String,
+    pub count: u64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub avg_ms: f64,
+}
+
+/// Commands after which `metrics.enabled` may have changed (a vault was opened or a setting written)
+const SETTINGS_COMMANDS: &[&str] = &[
+    "init_database",
+    "save_ui_state",
+    "save_ui_state_bool",
+    "save_ui_state_int",
+    "save_ui_state_json",
+    "delete_setting",
+    "delete_settings_by_prefix",
+];
+
+/// Setting `metrics.enabled` = "true" turns timing on (re-read after SETTINGS_COMMANDS, so it applies immediately)
+fn metrics_enabled(db: &DbConnection) -> bool {
+    let conn_guard = db.0.lock().unwrap();
+    let conn = match conn_guard.as_ref() {
+        Some(conn) => conn,
+        None => return false,
+    };
+    conn.prepare_cached("SELECT value FROM settings WHERE key = 'metrics.enabled'")
+        .and_then(|mut stmt| stmt.query_row([], |row| row.get::<_, String>(0)))
+        .map(|value| value.trim() == "true")
+        .unwrap_or(false)
+}
+
+/// Wrap the generated invoke handler so every command is timed while metrics are enabled
+/// All commands are synchronous, so each has finished by the time the inner handler returns
+pub fn timed<R, F>(handler: F) -> impl Fn(Invoke<R>) + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) + Send + Sync + 'static,
+{
+    move |invoke: Invoke<R>| {
+        let command = invoke.message.command().to_string();
+        let app = invoke.message.window().app_handle();
+        
+        let started = Instant::now();
+        handler(invoke);
+        let elapsed = started.elapsed();
+        
+        let metrics = app.state::<CommandMetrics>();
+        if SETTINGS_COMMANDS.contains(&command.as_str()) {
+            let enabled = metrics_enabled(&app.state::<DbConnection>());
+            metrics.enabled.store(enabled, Ordering::Relaxed);
+        }
+        if metrics.enabled.load(Ordering::Relaxed) {
+            metrics.record(&command, elapsed);
+        }
+    }
+}
+
+/// count/min/max/avg per command since launch (or the last reset), slowest average first
+#[tauri::command]
+pub fn get_command_metrics(metrics: State<CommandMetrics>) -> Result<Vec<CommandMetric>, String> {
+    let timings = metrics.timings.lock().unwrap();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    
+    let mut rows: Vec<CommandMetric> = timings
+        .iter()
+        .map(|(command, timing)| CommandMetric {
+            command: command.clone(),
+            count: timing.count,
+            min_ms: ms(timing.min),
+            max_ms: ms(timing.max),
+            avg_ms: ms(timing.total) / timing.count as f64,
+        })
+        .collect();
+    rows.sort_by(|a, b| b.avg_ms.total_cmp(&a.avg_ms));
+    
+    Ok(rows)
+}
+
+/// Forget every recorded timing (e.g. before measuring a specific workflow)
+#[tauri::command]
+pub fn reset_command_metrics(metrics: State<CommandMetrics>) -> Result<String, String> {
+    metrics.timings.lock().unwrap().clear();
+    Ok("Command metrics reset".to_string())
+}
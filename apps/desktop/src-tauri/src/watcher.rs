@@ -0,0 +1,113 @@
+use crate::database::{DbConnection, DbError};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+/// Emitted (with the database path) when another process has written to the open database
+pub const EXTERNAL_CHANGE_EVENT: &str = "database-changed-externally";
+
+/// Quiet period that ends a burst of file events (one SQLite commit touches several files)
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// A steady stream of writes still gets reported at least this often
+const MAX_DEBOUNCE: Duration = Duration::from_secs(3);
+
+/// The active file watcher, if start_watching has been called
+/// Dropping it closes the event channel, which ends the debounce thread
+pub struct DbWatcher(pub Mutex<Option<RecommendedWatcher>>);
+
+/// File names SQLite writes for a database at `db_path` (the main file and its WAL)
+fn watched_names(db_path: &Path) -> Vec<OsString> {
+    let name = db_path.file_name().unwrap_or_default().to_owned();
+    let mut wal = name.clone();
+    wal.push("-wal");
+    vec![name, wal]
+}
+
+/// `PRAGMA data_version` only changes when *another* connection commits,
+/// which is exactly "outside our own writes". Paired with the open database's path, since the
+/// counter is only comparable within one vault. None when the database is closed
+fn data_version(db: &DbConnection) -> Option<(String, i64)> {
+    let conn_guard = db.0.lock().unwrap();
+    let conn = conn_guard.as_ref()?;
+    let version = conn.query_row("PRAGMA data_version", [], |row| row.get(0)).ok()?;
+    Some((conn.path().unwrap_or_default().to_string(), version))
+}
+
+/// Watch the open database's .db / .db-wal files and emit EXTERNAL_CHANGE_EVENT when another
+/// process commits to it, so the frontend can offer a reload. Calling it again restarts the watch
+#[tauri::command]
+pub fn start_watching(
+    app: AppHandle,
+    state: State<DbConnection>,
+    watcher: State<DbWatcher>,
+) -> Result<String, String> {
+    let db_path = {
+        let conn_guard = state.0.lock().unwrap();
+        let conn = conn_guard.as_ref().ok_or(DbError::NotInitialized)?;
+        match conn.path() {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => return Err("In-memory databases can't be watched".to_string()),
+        }
+    };
+    let dir = db_path
+        .parent()
+        .ok_or_else(|| format!("Database path '{}' has no parent directory", db_path.display()))?
+        .to_path_buf();
+    
+    // Only forward events for our files; the directory may hold unrelated ones
+    let names = watched_names(&db_path);
+    let (tx, rx) = mpsc::channel::<()>();
+    let mut file_watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        if let Ok(event) = result {
+            let ours = event
+                .paths
+                .iter()
+                .any(|path| path.file_name().is_some_and(|name| names.iter().any(|n| n == name)));
+            if ours {
+                let _ = tx.send(());
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    // The directory, not the files: the WAL is created and removed as the database is used
+    file_watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+    
+    let mut last_version = data_version(&state);
+    let event_path = db_path.display().to_string();
+    std::thread::spawn(move || {
+        while rx.recv().is_ok() {
+            let burst_started = Instant::now();
+            while burst_started.elapsed() < MAX_DEBOUNCE && rx.recv_timeout(DEBOUNCE).is_ok() {}
+    
+            // A close/reopen resets data_version, so only compare two readings of the same vault;
+            // after init_database switches vaults the readings never match and nothing is emitted
+            let version = data_version(&app.state::<DbConnection>());
+            if let (Some((previous_path, previous)), Some((current_path, current))) = (&last_version, &version) {
+                if previous_path == current_path && previous != current {
+                    println!("🔄 Database changed externally: {}", event_path);
+                    let _ = app.emit_all(EXTERNAL_CHANGE_EVENT, event_path.clone());
+                }
+            }
+            last_version = version;
+        }
+    });
+    
+    // Replacing a previous watcher drops it, which stops its thread
+    *watcher.0.lock().unwrap() = Some(file_watcher);
+    
+    println!("👀 Watching for external changes: {}", db_path.display());
+    Ok(format!("Watching {}", db_path.display()))
+}
+
+/// Stop the watcher started by start_watching (no-op if none is running)
+#[tauri::command]
+pub fn stop_watching(watcher: State<DbWatcher>) -> Result<String, String> {
+    let stopped = watcher.0.lock().unwrap().take().is_some();
+    Ok(if stopped { "Stopped watching".to_string() } else { "Not watching".to_string() })
+}
@@ -18,18 +18,27 @@ export function getDatabasePath(): string | null {
   return `${folder}/clutter.db`;
 }
 
+export interface InitResult {
+  path: string;
+  schemaVersion: number;
+  appSchemaVersion: number;
+  migrated: boolean;
+  readOnly: boolean;
+}
+
 /**
  * Initialize the SQLite database
  * Creates tables and indexes if they don't exist
+ * schemaVersion > appSchemaVersion means the file comes from a newer app version
  */
-export async function initDatabase(): Promise<string> {
+export async function initDatabase(): Promise<InitResult> {
   const dbPath = getDatabasePath();
   if (!dbPath) {
     throw new Error('No storage folder configured');
   }
 
   try {
-    const result = await invoke<string>('init_database', { dbPath });
+    const result = await invoke<InitResult>('init_database', { dbPath });
     return result;
   } catch (error) {
     console.error('❌ Database init error:', error);
@@ -136,7 +145,7 @@ export async function loadAllNotesFromDatabase(): Promise<Note[]> {
  */
 export async function searchNotesInDatabase(query: string): Promise<Note[]> {
   try {
-    const notes = await invoke<Note[]>('search_notes', { query });
+    const { notes } = await invoke<{ notes: Note[]; total: number }>('search_notes', { query });
     return notes;
   } catch (error) {
     console.error('❌ SQLite search error:', error);
@@ -240,7 +249,7 @@ export async function loadAllTagsFromDatabase(): Promise<Tag[]> {
  */
 export async function deleteTagFromDatabase(tagName: string): Promise<void> {
   try {
-    await invoke<string>('delete_tag', { tagName });
+    await invoke<{ noteIds: string[]; folderIds: string[] }>('delete_tag', { tagName });
     console.log(`✅ Deleted tag "${tagName}" from database`);
   } catch (error) {
     console.error('❌ SQLite delete tag error:', error);
@@ -262,13 +271,19 @@ export async function deleteNotePermanently(noteId: string): Promise<void> {
   }
 }
 
+export type OrphanPolicy = 'reparent_to_root' | 'reparent_to_parent' | 'delete';
+
 /**
  * Permanently delete a folder from SQLite database
  * This removes the folder record and all associated junction table entries
+ * The folder's notes move to root by default (see OrphanPolicy)
  */
-export async function deleteFolderPermanently(folderId: string): Promise<void> {
+export async function deleteFolderPermanently(
+  folderId: string,
+  orphanPolicy: OrphanPolicy = 'reparent_to_root'
+): Promise<void> {
   try {
-    await invoke<string>('delete_folder_permanently', { folderId });
+    await invoke<{ reparented: number; trashed: number }>('delete_folder_permanently', { folderId, orphanPolicy });
     console.log(`✅ Permanently deleted folder "${folderId}" from database`);
   } catch (error) {
     console.error('❌ SQLite delete folder error:', error);